@@ -0,0 +1,280 @@
+use crate::utils::split_timestamp;
+use std::{fmt, str::FromStr};
+
+/// How [`crate::Transcript::as_ass_with`] styles the subtitles. Sizes and margins are in pixels of
+/// a 1080p video, and get scaled to the size of the video the subtitles are shown on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssOptions {
+    /// The style of every line, and the one the other styles are based on.
+    pub style: AssStyle,
+    /// Which lines get a style of their own.
+    pub styling: AssStyling,
+}
+
+impl Default for AssOptions {
+    fn default() -> Self {
+        Self {
+            style: AssStyle::default(),
+            styling: AssStyling::Single,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssStyle {
+    pub font: String,
+    pub font_size: u32,
+    pub bold: bool,
+    /// Colour of the text.
+    pub colour: Colour,
+    /// Colour of the words that haven't been spoken yet, when highlighting words.
+    pub karaoke_colour: Colour,
+    pub outline_colour: Colour,
+    pub shadow_colour: Colour,
+    /// Width of the outline around the text.
+    pub outline: f32,
+    /// How far the shadow is from the text.
+    pub shadow: f32,
+    pub alignment: Alignment,
+    pub margin_left: u32,
+    pub margin_right: u32,
+    /// Distance from the top or bottom of the video, depending on the alignment.
+    pub margin_vertical: u32,
+}
+
+impl Default for AssStyle {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            font_size: 64,
+            outline: 3.0,
+            shadow: 0.0,
+            margin_left: 60,
+            margin_right: 60,
+            margin_vertical: 60,
+            font: "Arial".to_string(),
+            alignment: Alignment::Bottom,
+            colour: Colour::rgb(255, 255, 255),
+            karaoke_colour: Colour::rgb(160, 160, 160),
+            outline_colour: Colour::rgb(0, 0, 0),
+            shadow_colour: Colour::rgba(0, 0, 0, 128),
+        }
+    }
+}
+
+impl AssStyle {
+    /// This style under another name, with another text colour.
+    fn recoloured(&self, colour: Colour) -> Self {
+        Self {
+            colour,
+            ..self.clone()
+        }
+    }
+
+    fn to_line(&self, name: &str) -> String {
+        format!(
+            "Style: {name},{},{},{},{},{},{},{},0,0,0,100,100,0,0,1,{},{},{},{},{},{},1",
+            self.font.replace(',', " "),
+            self.font_size,
+            self.colour.to_ass(),
+            self.karaoke_colour.to_ass(),
+            self.outline_colour.to_ass(),
+            self.shadow_colour.to_ass(),
+            if self.bold { -1 } else { 0 },
+            self.outline,
+            self.shadow,
+            self.alignment as u8,
+            self.margin_left,
+            self.margin_right,
+            self.margin_vertical,
+        )
+    }
+}
+
+/// Where on the video the subtitles are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Alignment {
+    BottomLeft = 1,
+    Bottom = 2,
+    BottomRight = 3,
+    MiddleLeft = 4,
+    Middle = 5,
+    MiddleRight = 6,
+    TopLeft = 7,
+    Top = 8,
+    TopRight = 9,
+}
+
+/// Which lines of the subtitles get a style of their own, in a different colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AssStyling {
+    /// Every line has the same style
+    Single,
+    /// Each speaker has their own style, when the transcript has been diarized
+    Speaker,
+    /// Lines whisper wasn't confident about are coloured in yellow, or red when it's even less
+    /// sure
+    Confidence,
+}
+
+/// Lines with an average token probability below these get the medium and low confidence styles.
+const MEDIUM_CONFIDENCE: f32 = 0.8;
+const LOW_CONFIDENCE: f32 = 0.5;
+
+/// Text colours of the speaker styles, picked in turn.
+const SPEAKER_COLOURS: &[Colour] = &[
+    Colour::rgb(255, 255, 255),
+    Colour::rgb(255, 230, 90),
+    Colour::rgb(110, 210, 255),
+    Colour::rgb(140, 240, 140),
+    Colour::rgb(255, 160, 200),
+    Colour::rgb(255, 180, 100),
+];
+
+impl AssOptions {
+    /// The name of the style for a line, given its speaker and how confident whisper was in it.
+    pub(crate) fn style_for(&self, speaker: Option<usize>, confidence: Option<f32>) -> String {
+        match (self.styling, speaker, confidence) {
+            (AssStyling::Speaker, Some(speaker), _) => format!("Speaker {}", speaker + 1),
+            (AssStyling::Confidence, _, Some(p)) if p < LOW_CONFIDENCE => {
+                "Low confidence".to_string()
+            }
+            (AssStyling::Confidence, _, Some(p)) if p < MEDIUM_CONFIDENCE => {
+                "Medium confidence".to_string()
+            }
+            _ => "Default".to_string(),
+        }
+    }
+
+    /// Everything that comes before the lines, with a style for every `speakers`.
+    pub(crate) fn header(&self, speakers: usize) -> String {
+        let mut styles = vec![self.style.to_line("Default")];
+        match self.styling {
+            AssStyling::Single => {}
+            AssStyling::Speaker => {
+                styles.extend((0..speakers).map(|speaker| {
+                    self.style
+                        .recoloured(SPEAKER_COLOURS[speaker % SPEAKER_COLOURS.len()])
+                        .to_line(&format!("Speaker {}", speaker + 1))
+                }));
+            }
+            AssStyling::Confidence => {
+                styles.push(
+                    self.style
+                        .recoloured(Colour::rgb(255, 220, 80))
+                        .to_line("Medium confidence"),
+                );
+                styles.push(
+                    self.style
+                        .recoloured(Colour::rgb(255, 110, 90))
+                        .to_line("Low confidence"),
+                );
+            }
+        }
+
+        format!(
+            "[Script Info]
+ScriptType: v4.00+
+PlayResX: 1920
+PlayResY: 1080
+WrapStyle: 0
+ScaledBorderAndShadow: yes
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+{}
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+",
+            styles.join("\n")
+        )
+    }
+}
+
+/// A colour, written like `#RRGGBB` or `#RRGGBBAA`, where `AA` is how opaque it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colour {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Colour {
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Self::rgba(red, green, blue, 255)
+    }
+
+    pub const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /// Formats the colour the way ASS expects, like `&H00FFFFFF`. ASS colours are in reverse
+    /// order, with transparency instead of opacity.
+    fn to_ass(self) -> String {
+        format!(
+            "&H{:02X}{:02X}{:02X}{:02X}",
+            255 - self.alpha,
+            self.blue,
+            self.green,
+            self.red
+        )
+    }
+}
+
+impl FromStr for Colour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .ok_or_else(|| format!("invalid colour `{s}`, expected #RRGGBB or #RRGGBBAA"))
+        };
+
+        match hex.len() {
+            6 => Ok(Self::rgb(channel(0)?, channel(2)?, channel(4)?)),
+            8 => Ok(Self::rgba(
+                channel(0)?,
+                channel(2)?,
+                channel(4)?,
+                channel(6)?,
+            )),
+            _ => Err(format!(
+                "invalid colour `{s}`, expected #RRGGBB or #RRGGBBAA"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.red, self.green, self.blue)?;
+        if self.alpha != 255 {
+            write!(f, "{:02X}", self.alpha)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps text from being read as ASS override tags, and turns line breaks into ASS ones.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('{', "(")
+        .replace('}', ")")
+        .replace('\\', "\u{2216}")
+        .replace('\n', "\\N")
+}
+
+/// Formats a timestamp (in centiseconds) the way ASS expects, like `0:01:02.50`.
+pub(crate) fn timestamp(ticks: i64) -> String {
+    let (hours, minutes, seconds, centiseconds) = split_timestamp(ticks);
+    format!("{hours}:{minutes:02}:{seconds:02}.{centiseconds:02}")
+}
@@ -0,0 +1,166 @@
+use crate::transcript::Utternace;
+use std::{
+    ffi::{c_int, c_void, CStr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use whisper_rs::FullParams;
+use whisper_rs_sys::{whisper_context, whisper_state};
+
+/// Stops a transcription that is running on another thread. Clones share the same state, so
+/// cancelling any of them cancels them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the transcription to stop. Whisper checks in before encoding each 30 second window,
+    /// so it can take a moment for it to return [`crate::Error::Cancelled`].
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+type ProgressCallback<'a> = Box<dyn FnMut(f32) + 'a>;
+type SegmentCallback<'a> = Box<dyn FnMut(&Utternace) + 'a>;
+
+/// Hooks into a transcription while it runs, for [`crate::Whisper::transcribe_with`].
+#[derive(Default)]
+pub struct Callbacks<'a> {
+    progress: Option<ProgressCallback<'a>>,
+    segment: Option<SegmentCallback<'a>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<'a> Callbacks<'a> {
+    /// Calls `callback` with how far along the transcription is, from 0 to 1.
+    #[must_use]
+    pub fn on_progress(mut self, callback: impl FnMut(f32) + 'a) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Calls `callback` with each utterance as soon as it's transcribed. These utterances don't
+    /// have any tokens, and aren't split into words.
+    #[must_use]
+    pub fn on_segment(mut self, callback: impl FnMut(&Utternace) + 'a) -> Self {
+        self.segment = Some(Box::new(callback));
+        self
+    }
+
+    /// Stops the transcription when `token` is cancelled.
+    #[must_use]
+    pub fn cancel_with(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    pub(crate) fn report_progress(&mut self, progress: f32) {
+        if let Some(callback) = &mut self.progress {
+            callback(progress);
+        }
+    }
+}
+
+/// What the whisper.cpp callbacks below get as their user data.
+pub(crate) struct Hooks<'a, 'b> {
+    pub callbacks: &'b mut Callbacks<'a>,
+    /// Where the audio being transcribed starts in the recording, in ticks, to move the
+    /// timestamps of new segments back to where they are in it.
+    pub offset: i64,
+    /// How much of the whole transcription was done before this inference run, and how much of
+    /// it the run makes up, to report progress across several runs.
+    pub progress: (f32, f32),
+}
+
+impl Hooks<'_, '_> {
+    /// Points the callbacks of `params` to `self`.
+    ///
+    /// # Safety
+    ///
+    /// `self` has to outlive every inference run that uses `params`.
+    pub unsafe fn install(&mut self, params: &mut FullParams) {
+        let user_data = std::ptr::from_mut(self).cast::<c_void>();
+
+        params.set_progress_callback(Some(report_progress));
+        params.set_progress_callback_user_data(user_data);
+        params.set_new_segment_callback(Some(report_segments));
+        params.set_new_segment_callback_user_data(user_data);
+        params.set_start_encoder_callback(Some(check_cancelled));
+        params.set_start_encoder_callback_user_data(user_data);
+    }
+}
+
+unsafe extern "C" fn report_progress(
+    _: *mut whisper_context,
+    _: *mut whisper_state,
+    progress: c_int,
+    user_data: *mut c_void,
+) {
+    let hooks = &mut *user_data.cast::<Hooks>();
+
+    let (done, share) = hooks.progress;
+    #[allow(clippy::cast_precision_loss)]
+    hooks
+        .callbacks
+        .report_progress(share.mul_add(progress as f32 / 100.0, done));
+}
+
+unsafe extern "C" fn report_segments(
+    _: *mut whisper_context,
+    state: *mut whisper_state,
+    new: c_int,
+    user_data: *mut c_void,
+) {
+    let hooks = &mut *user_data.cast::<Hooks>();
+    let segments = whisper_rs_sys::whisper_full_n_segments_from_state(state);
+
+    let Some(callback) = &mut hooks.callbacks.segment else {
+        return;
+    };
+
+    for segment in (segments - new).max(0)..segments {
+        let text = whisper_rs_sys::whisper_full_get_segment_text_from_state(state, segment);
+        let mut utterance = Utternace {
+            text: if text.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(text).to_string_lossy().into_owned()
+            },
+            start: whisper_rs_sys::whisper_full_get_segment_t0_from_state(state, segment),
+            stop: whisper_rs_sys::whisper_full_get_segment_t1_from_state(state, segment),
+            tokens: Vec::new(),
+            speaker: None,
+            confidence: None,
+            utterance: None,
+        };
+        utterance.shift(hooks.offset);
+
+        callback(&utterance);
+    }
+}
+
+/// Called before each window gets encoded. Returning false stops the inference run.
+unsafe extern "C" fn check_cancelled(
+    _: *mut whisper_context,
+    _: *mut whisper_state,
+    user_data: *mut c_void,
+) -> bool {
+    let hooks = &*user_data.cast::<Hooks>();
+    !hooks.callbacks.is_cancelled()
+}
@@ -0,0 +1,76 @@
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+/// A table of SHA-256 checksums, in the format used by `sha256sum` (so it can also be checked with
+/// `sha256sum -c`).
+#[derive(Debug, Default, Clone)]
+pub struct Checksums(BTreeMap<String, String>);
+
+impl Checksums {
+    /// Reads a checksum table, treating a missing file as an empty one.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        Self(
+            contents
+                .lines()
+                .filter_map(|line| {
+                    let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+                    // `sha256sum` marks files hashed in binary mode with an asterisk
+                    let file = file.trim_start().trim_start_matches('*');
+
+                    is_sha256(hash).then(|| (file.to_string(), hash.to_lowercase()))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn get(&self, file: &str) -> Option<&str> {
+        self.0.get(file).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, file: String, hash: String) {
+        self.0.insert(file, hash);
+    }
+
+    pub fn remove(&mut self, file: &str) -> Option<String> {
+        self.0.remove(file)
+    }
+}
+
+impl std::fmt::Display for Checksums {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (file, hash) in &self.0 {
+            writeln!(f, "{hash}  {file}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the SHA-256 checksum of a file, as a lowercase hex string.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub(crate) fn is_sha256(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
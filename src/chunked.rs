@@ -0,0 +1,175 @@
+use crate::{
+    decoder::AudioStream,
+    error::{Error, Result},
+    transcript::Utternace,
+    whisper::{self, Decoding, MIN_INFERENCE_SAMPLES},
+};
+use std::{borrow::Cow, collections::VecDeque, time::Duration};
+use whisper_rs::WhisperState;
+
+/// Number of samples in one of whisper's timestamp units (10ms at 16kHz).
+pub(crate) const SAMPLES_PER_TICK: usize = 160;
+
+/// How long audio is split into windows for [`crate::Whisper::transcribe_chunked`].
+#[derive(Debug, Clone, Copy)]
+pub struct Chunking {
+    /// Length of each window that gets transcribed in one go.
+    pub length: Duration,
+    /// Audio at the end of a window that is transcribed again as part of the next one, so
+    /// segments cut off by the window boundary are not lost.
+    pub overlap: Duration,
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self {
+            length: Duration::from_secs(120),
+            overlap: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Chunking {
+    /// Checks that audio can be transcribed with these windows, which
+    /// [`crate::Whisper::transcribe_chunked`] does before starting.
+    pub fn validate(&self) -> Result<()> {
+        if duration_to_samples(self.length) == 0 {
+            return Err(Error::InvalidOptions(
+                "the window has to be at least a millisecond long".to_string(),
+            ));
+        }
+        if self.overlap >= self.length {
+            return Err(Error::InvalidOptions(
+                "the overlap between windows has to be shorter than a window".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// An iterator over the utterances of an audio file, transcribed one window at a time.
+pub struct Chunks {
+    chunking: Chunking,
+    source: AudioStream,
+    state: WhisperState,
+    decoding: Decoding,
+    buffer: Vec<f32>,
+    offset: usize,
+    pending: VecDeque<Utternace>,
+    exhausted: bool,
+    finished: bool,
+}
+
+impl Chunks {
+    pub(crate) fn new(
+        state: WhisperState,
+        source: AudioStream,
+        chunking: Chunking,
+        decoding: Decoding,
+    ) -> Self {
+        Self {
+            state,
+            source,
+            chunking,
+            offset: 0,
+            finished: false,
+            exhausted: false,
+            buffer: Vec::new(),
+            pending: VecDeque::new(),
+            decoding,
+        }
+    }
+
+    fn fill_buffer(&mut self) -> Result<()> {
+        let length = duration_to_samples(self.chunking.length);
+
+        while !self.exhausted && self.buffer.len() < length {
+            match self.source.next() {
+                Some(chunk) => self.buffer.extend(chunk?),
+                None => self.exhausted = true,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn transcribe_window(&mut self) -> Result<()> {
+        self.fill_buffer()?;
+
+        let length = duration_to_samples(self.chunking.length).min(self.buffer.len());
+        let is_last = self.exhausted && length == self.buffer.len();
+        if length == 0 {
+            self.finished = true;
+            return Ok(());
+        }
+
+        // whisper skips audio shorter than a second, so pad the last window with silence
+        let mut samples = Cow::Borrowed(&self.buffer[..length]);
+        if samples.len() < MIN_INFERENCE_SAMPLES {
+            samples.to_mut().resize(MIN_INFERENCE_SAMPLES, 0.0);
+        }
+        self.state.full(self.decoding.params(), &samples)?;
+
+        let boundary = length.saturating_sub(duration_to_samples(self.chunking.overlap));
+
+        #[allow(clippy::cast_possible_wrap)]
+        let offset = (self.offset / SAMPLES_PER_TICK) as i64;
+
+        let mut consumed = 0;
+        for mut utterance in whisper::segments(&self.state, false, false)? {
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+            let stop_sample = utterance.stop.max(0) as usize * SAMPLES_PER_TICK;
+            if !is_last && stop_sample > boundary {
+                break;
+            }
+
+            utterance.start += offset;
+            utterance.stop += offset;
+            self.pending.push_back(utterance);
+            consumed = stop_sample;
+        }
+
+        if is_last {
+            self.finished = true;
+            return Ok(());
+        }
+
+        // If nothing could be committed, skip ahead so we don't transcribe the same window forever.
+        if consumed == 0 {
+            consumed = (boundary - boundary % SAMPLES_PER_TICK).max(SAMPLES_PER_TICK);
+        }
+
+        let consumed = consumed.min(self.buffer.len());
+        self.buffer.drain(..consumed);
+        self.offset += consumed;
+
+        Ok(())
+    }
+}
+
+impl Iterator for Chunks {
+    type Item = Result<Utternace>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(utterance) = self.pending.pop_front() {
+                return Some(Ok(utterance));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            if let Err(e) = self.transcribe_window() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) const fn duration_to_samples(duration: Duration) -> usize {
+    (duration.as_millis() * 16) as usize
+}
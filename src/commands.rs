@@ -0,0 +1,8 @@
+pub mod decoding;
+pub mod detect_language;
+pub mod listen;
+pub mod models;
+pub mod output;
+pub mod serve;
+pub mod subtitles;
+pub mod transcribe;
@@ -0,0 +1,69 @@
+use whisper_cli::TranscribeOptions;
+
+#[derive(clap::Args)]
+#[command(next_help_heading = "Decoding")]
+pub struct Args {
+    /// Use beam search with this many beams, instead of greedy decoding
+    #[clap(long)]
+    beam_size: Option<usize>,
+
+    /// Beam search patience factor
+    #[clap(long, requires = "beam_size")]
+    patience: Option<f32>,
+
+    /// Number of candidates to pick the best from when sampling with a non-zero temperature
+    #[clap(long, default_value_t = TranscribeOptions::default().best_of)]
+    best_of: usize,
+
+    /// Sampling temperature
+    #[clap(long, default_value_t = TranscribeOptions::default().temperature)]
+    temperature: f32,
+
+    /// How much to raise the temperature by when decoding fails, to try again. 0 disables retries.
+    #[clap(long, default_value_t = TranscribeOptions::default().temperature_increment)]
+    temperature_increment: f32,
+
+    /// Retry decoding if the entropy of the text is higher than this
+    #[clap(long, default_value_t = TranscribeOptions::default().entropy_threshold)]
+    entropy_threshold: f32,
+
+    /// Retry decoding if the average log probability of the text is lower than this
+    #[clap(long, allow_negative_numbers = true, default_value_t = TranscribeOptions::default().logprob_threshold)]
+    logprob_threshold: f32,
+
+    /// Skip segments whose probability of containing no speech is higher than this
+    #[clap(long, default_value_t = TranscribeOptions::default().no_speech_threshold)]
+    no_speech_threshold: f32,
+
+    /// Text to guide the transcript with, like the vocabulary or spelling to expect
+    #[clap(long)]
+    initial_prompt: Option<String>,
+
+    /// Comma-separated ids of tokens that should never be generated. -1 suppresses non-speech symbols.
+    #[clap(long, value_delimiter = ',', allow_negative_numbers = true)]
+    suppress_tokens: Vec<i32>,
+
+    /// Split segments so they are at most this many characters long
+    #[clap(long)]
+    max_segment_length: Option<usize>,
+}
+
+impl Args {
+    pub fn options(&self, translate: bool, word_timestamps: bool) -> TranscribeOptions {
+        TranscribeOptions {
+            translate,
+            word_timestamps,
+            best_of: self.best_of,
+            patience: self.patience,
+            beam_size: self.beam_size,
+            temperature: self.temperature,
+            entropy_threshold: self.entropy_threshold,
+            logprob_threshold: self.logprob_threshold,
+            no_speech_threshold: self.no_speech_threshold,
+            initial_prompt: self.initial_prompt.clone(),
+            suppress_tokens: self.suppress_tokens.clone(),
+            max_segment_length: self.max_segment_length,
+            temperature_increment: self.temperature_increment,
+        }
+    }
+}
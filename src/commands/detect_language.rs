@@ -0,0 +1,70 @@
+use super::transcribe::collect_files;
+use crate::ModelArgs;
+use anyhow::{bail, Result};
+use serde_json::json;
+use std::time::Duration;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[clap(flatten)]
+    model: ModelArgs,
+
+    /// Audio files, directories or glob patterns to detect the language of
+    #[clap(required = true)]
+    audio: Vec<String>,
+
+    /// Seconds of audio, from the start of each file, to detect the language from
+    #[clap(short, long, default_value = "30")]
+    duration: u64,
+
+    /// Print each result as a line of JSON, including the probability of every language
+    #[clap(long, default_value = "false")]
+    json: bool,
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    let files = collect_files(&args.audio)?;
+    let whisper = args.model.load(None).await?;
+    if !whisper.is_multilingual() {
+        bail!("Language detection needs a multilingual model.");
+    }
+
+    let mut failures = Vec::new();
+    for audio in &files {
+        let detection = match whisper.detect_language(audio, Duration::from_secs(args.duration)) {
+            Ok(detection) => detection,
+            Err(e) => {
+                eprintln!("{}: {e}", audio.display());
+                failures.push(e);
+                continue;
+            }
+        };
+
+        if args.json {
+            println!(
+                "{}",
+                json!({
+                    "file": audio,
+                    "language": detection.language,
+                    "probabilities": detection.probabilities,
+                })
+            );
+        } else {
+            let language: &str = detection.language.into();
+            let probability = detection.probabilities[&detection.language];
+            println!(
+                "{}: {language} ({:.1}%)",
+                audio.display(),
+                probability * 100.0
+            );
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    let count = failures.len();
+    Err(anyhow::Error::from(failures.swap_remove(0))
+        .context(format!("{count} of {} files failed", files.len())))
+}
@@ -0,0 +1,158 @@
+use super::decoding;
+use crate::ModelArgs;
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Read, Write},
+    path::PathBuf,
+    time::Duration,
+};
+use whisper_cli::{format_timestamp, Language, LiveEvent, LiveOptions};
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum SampleFormat {
+    /// Signed 16-bit little-endian integers
+    #[clap(name = "s16le")]
+    S16Le,
+    /// 32-bit little-endian floats
+    #[clap(name = "f32le")]
+    F32Le,
+}
+
+impl SampleFormat {
+    const fn width(self) -> usize {
+        match self {
+            Self::S16Le => 2,
+            Self::F32Le => 4,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(self.width())
+            .map(|sample| match self {
+                Self::S16Le => f32::from(i16::from_le_bytes([sample[0], sample[1]])) / 32768.0,
+                Self::F32Le => f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+            })
+            .collect()
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[clap(flatten)]
+    model: ModelArgs,
+
+    /// Language spoken in the audio. Attempts to auto-detect by default.
+    #[clap(short, long)]
+    lang: Option<Language>,
+
+    /// File or named pipe to read audio from. Reads from stdin by default.
+    #[clap(short, long)]
+    input: Option<PathBuf>,
+
+    /// Encoding of the incoming samples
+    #[clap(short, long, default_value = "s16le")]
+    format: SampleFormat,
+
+    /// Sample rate of the incoming audio
+    #[clap(short, long, default_value = "16000", value_parser = clap::value_parser!(u32).range(1..))]
+    sample_rate: u32,
+
+    /// Number of interleaved channels in the incoming audio
+    #[clap(short, long, default_value = "1", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    channels: usize,
+
+    /// Milliseconds of new audio to wait for before transcribing again
+    #[clap(long, default_value = "3000")]
+    step: u64,
+
+    /// Milliseconds of audio to transcribe at once before finalizing the text
+    #[clap(long, default_value = "15000")]
+    length: u64,
+
+    /// Milliseconds of audio to carry over when the text gets finalized
+    #[clap(long, default_value = "2000")]
+    keep: u64,
+
+    /// Toggle translation
+    #[clap(short, long, default_value = "false")]
+    translate: bool,
+
+    #[clap(flatten)]
+    decoding: decoding::Args,
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    let options = LiveOptions {
+        channels: args.channels,
+        sample_rate: args.sample_rate,
+        step: Duration::from_millis(args.step),
+        keep: Duration::from_millis(args.keep),
+        length: Duration::from_millis(args.length),
+    };
+    options.validate()?;
+
+    let whisper = args.model.load(args.lang).await?;
+    let mut input: Box<dyn Read> = match args.input {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin().lock()),
+    };
+
+    let mut live = whisper.listen(&args.decoding.options(args.translate, false), options)?;
+
+    // read ~100ms of audio at a time
+    let frame = args.format.width() * args.channels;
+    let mut buffer = vec![0; frame * (args.sample_rate as usize / 10).max(1)];
+    let mut filled = 0;
+
+    loop {
+        let read = match input.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        filled += read;
+        let usable = filled - filled % frame;
+        let events = live.push(&args.format.decode(&buffer[..usable]))?;
+
+        buffer.copy_within(usable..filled, 0);
+        filled -= usable;
+
+        print_events(events);
+    }
+
+    print_events(live.finish()?);
+
+    Ok(())
+}
+
+fn print_events(events: Vec<LiveEvent>) {
+    let mut stderr = io::stderr();
+    let interactive = stderr.is_terminal();
+
+    for event in events {
+        match event {
+            LiveEvent::Provisional(text) => {
+                if interactive {
+                    let _ = write!(stderr, "\r\x1b[2K{}", text.trim());
+                    let _ = stderr.flush();
+                }
+            }
+            LiveEvent::Final(utterance) => {
+                if interactive {
+                    let _ = write!(stderr, "\r\x1b[2K");
+                }
+
+                println!(
+                    "[{} --> {}] {}",
+                    format_timestamp(utterance.start, false, "."),
+                    format_timestamp(utterance.stop, false, "."),
+                    utterance.text.trim()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{bail, Result};
+use clap::{Subcommand, ValueEnum};
+use indicatif::HumanBytes;
+use std::{fs, path::PathBuf};
+use whisper_cli::{models_dir, Checksums, Model, Variant, Verification};
+
+use crate::SourceArgs;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the available models, and which of them are downloaded
+    List,
+    /// Download models, so they're available offline
+    Download {
+        #[clap(required = true)]
+        models: Vec<Variant>,
+    },
+    /// Delete downloaded models
+    Rm {
+        #[clap(required = true)]
+        models: Vec<Variant>,
+    },
+    /// Check downloaded models for corruption. Checks every downloaded model by default, and fails
+    /// for models without a known checksum.
+    Verify {
+        models: Vec<Variant>,
+
+        /// File with the expected SHA-256 checksums, in the format used by `sha256sum`. Defaults
+        /// to the checksums the models were published with, or else the ones recorded when they
+        /// were downloaded.
+        #[clap(long)]
+        checksums: Option<PathBuf>,
+    },
+    /// Print where models are stored, or the path to a specific model
+    Path { model: Option<Variant> },
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    match args.command {
+        Command::List => list(),
+        Command::Download { models } => download(&models, &args.source).await,
+        Command::Rm { models } => remove(&models),
+        Command::Verify { models, checksums } => verify(models, checksums),
+        Command::Path { model } => {
+            let path = match model {
+                Some(model) => model.get_path()?,
+                None => models_dir()?,
+            };
+
+            println!("{}", path.display());
+            Ok(())
+        }
+    }
+}
+
+fn list() -> Result<()> {
+    println!("{:<20} {:>10}", "MODEL", "SIZE");
+
+    for &variant in Variant::value_variants() {
+        let on_disk = fs::metadata(variant.get_path()?).map_or_else(
+            |_| "-".to_string(),
+            |metadata| HumanBytes(metadata.len()).to_string(),
+        );
+
+        println!("{:<20} {on_disk:>10}", variant.to_string());
+    }
+
+    Ok(())
+}
+
+async fn download(models: &[Variant], source: &SourceArgs) -> Result<()> {
+    for &variant in models {
+        let model = source.apply(Model::new(variant));
+        if model.is_downloaded()? {
+            println!("{variant} is already downloaded.");
+            continue;
+        }
+
+        model.download().await?;
+    }
+
+    Ok(())
+}
+
+fn remove(models: &[Variant]) -> Result<()> {
+    for &variant in models {
+        if Model::new(variant).remove()? {
+            println!("Deleted {variant}.");
+        } else {
+            println!("{variant} is not downloaded.");
+        }
+    }
+
+    Ok(())
+}
+
+fn verify(mut models: Vec<Variant>, checksums: Option<PathBuf>) -> Result<()> {
+    let checksums = checksums
+        .map(|path| fs::read_to_string(path).map(|contents| Checksums::parse(&contents)))
+        .transpose()?;
+
+    if models.is_empty() {
+        for &variant in Variant::value_variants() {
+            if Model::new(variant).is_downloaded()? {
+                models.push(variant);
+            }
+        }
+    }
+
+    let mut failed = 0;
+    for variant in models {
+        let model = Model::new(variant);
+        if !model.is_downloaded()? {
+            println!("{variant}: not downloaded");
+            failed += 1;
+            continue;
+        }
+
+        match model.verify(checksums.as_ref())? {
+            Verification::Valid => println!("{variant}: OK"),
+            Verification::Unknown { actual } => {
+                println!("{variant}: UNKNOWN (no known checksum, SHA-256 is {actual})");
+                failed += 1;
+            }
+            Verification::Mismatch { expected, actual } => {
+                println!("{variant}: FAILED (expected {expected}, got {actual})");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} models failed verification.");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use super::subtitles;
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use whisper_cli::{Ass, Formatter, OutputFormat, Transcript};
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum IfExists {
+    /// Keep existing files, and don't transcribe audio whose outputs all exist already
+    Skip,
+    /// Replace existing files
+    Overwrite,
+    /// Fail if any of the output files already exist
+    Error,
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Formats to save the transcript as
+    #[clap(
+        short,
+        long = "format",
+        value_delimiter = ',',
+        default_value = "txt,vtt,srt,json"
+    )]
+    formats: Vec<OutputFormat>,
+
+    /// Directory to save transcripts to. Defaults to the directory of each audio file.
+    #[clap(short, long)]
+    output_dir: Option<PathBuf>,
+
+    /// Name of the output files. `{file}` is replaced with the audio's file name, `{stem}` with
+    /// its file name without extension, and `{ext}` with the extension of the output format.
+    #[clap(long, default_value = "{file}.{ext}")]
+    output_template: String,
+
+    /// Print the transcripts to stdout instead of saving them
+    #[clap(long, default_value = "false")]
+    stdout: bool,
+
+    /// What to do when an output file already exists
+    #[clap(long, default_value = "overwrite")]
+    overwrite: IfExists,
+
+    #[clap(flatten)]
+    subtitles: subtitles::Args,
+}
+
+impl Args {
+    pub const fn to_stdout(&self) -> bool {
+        self.stdout
+    }
+
+    /// Checks the output files for `audio` against the overwrite policy, returning whether the
+    /// audio needs to be transcribed at all.
+    pub fn should_transcribe(&self, audio: &Path) -> Result<bool> {
+        if self.stdout {
+            return Ok(true);
+        }
+
+        let mut existing = Vec::new();
+        for format in &self.formats {
+            let path = self.path_for(audio, *format)?;
+            if path.exists() {
+                existing.push(path);
+            }
+        }
+
+        match self.overwrite {
+            IfExists::Overwrite => Ok(true),
+            IfExists::Skip => Ok(existing.len() < self.formats.len()),
+            IfExists::Error => existing.first().map_or(Ok(true), |path| {
+                Err(anyhow!("{} already exists", path.display()))
+            }),
+        }
+    }
+
+    /// Fails if any two output files of `files` would end up at the same path, like audio files
+    /// with the same name in different directories saved to one `--output-dir`.
+    pub fn check_collisions(&self, files: &[PathBuf]) -> Result<()> {
+        if self.stdout {
+            return Ok(());
+        }
+
+        let mut outputs = HashMap::new();
+        for audio in files {
+            for format in &self.formats {
+                let path = self.path_for(audio, *format)?;
+                if let Some(other) = outputs.insert(path.clone(), audio) {
+                    return Err(anyhow!(
+                        "{} and {} would both be saved as {}, change --output-dir or --output-template to tell them apart",
+                        other.display(),
+                        audio.display(),
+                        path.display()
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write(&self, audio: &Path, transcript: &Transcript) -> Result<()> {
+        let subtitles = self
+            .subtitles
+            .layout()
+            .filter(|_| self.formats.iter().any(|format| format.is_subtitle()))
+            .map(|layout| transcript.with_layout(&layout));
+        let render = |format: OutputFormat| {
+            let transcript = match &subtitles {
+                Some(subtitles) if format.is_subtitle() => subtitles,
+                _ => transcript,
+            };
+
+            // ASS subtitles are styled by the options under "Subtitles"
+            if format.name() == "ass" {
+                Ass(self.subtitles.ass_options()).render(transcript)
+            } else {
+                format.render(transcript)
+            }
+        };
+
+        if self.stdout {
+            let content = self
+                .formats
+                .iter()
+                .map(|format| render(*format))
+                .collect::<String>();
+
+            return Ok(io::stdout().lock().write_all(content.as_bytes())?);
+        }
+
+        for format in &self.formats {
+            let path = self.path_for(audio, *format)?;
+            if self.overwrite == IfExists::Skip && path.exists() {
+                continue;
+            }
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::write(path, render(*format))?;
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn path_for(&self, audio: &Path, format: OutputFormat) -> Result<PathBuf> {
+        let file_name = audio
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("invalid file name"))?;
+        let stem = audio
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(file_name);
+
+        let name = self
+            .output_template
+            .replace("{file}", file_name)
+            .replace("{stem}", stem)
+            .replace("{ext}", format.extension());
+
+        Ok(self.output_dir.as_ref().map_or_else(
+            || audio.with_file_name(&name),
+            |output_dir| output_dir.join(&name),
+        ))
+    }
+}
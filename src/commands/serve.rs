@@ -0,0 +1,1034 @@
+mod jobs;
+
+use super::decoding;
+use crate::ModelArgs;
+use anyhow::{anyhow, Result};
+use hyper::{
+    body::HttpBody,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use jobs::{Queue, Status, Submission};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    convert::Infallible,
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+use whisper_cli::{
+    decode_file, Callbacks, CancellationToken, EnergyVad, Error, Language, OutputFormat,
+    TranscribeOptions, Transcript, Whisper,
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[clap(flatten)]
+    model: ModelArgs,
+
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1")]
+    host: IpAddr,
+
+    /// Port to listen on
+    #[clap(short, long, default_value = "8000")]
+    port: u16,
+
+    /// Skip silent parts of the audio instead of transcribing them
+    #[clap(long, default_value = "false")]
+    vad: bool,
+
+    /// Number of requests to transcribe concurrently. Other requests wait for their turn.
+    #[clap(short, long, default_value = "1")]
+    jobs: usize,
+
+    /// Largest upload accepted, in megabytes
+    #[clap(long, default_value = "25")]
+    max_upload_size: u64,
+
+    /// Directory to keep queued jobs and their results in
+    #[clap(long, value_name = "DIR")]
+    queue_dir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    decoding: decoding::Args,
+}
+
+fn default_queue_dir() -> Result<PathBuf> {
+    let mut path =
+        dirs::data_local_dir().ok_or_else(|| anyhow!("could not find data directory"))?;
+    path.push("whisper");
+    path.push("jobs");
+
+    Ok(path)
+}
+
+/// Everything the request handlers share.
+struct State {
+    whisper: Whisper,
+    /// Options used for anything a request doesn't specify.
+    options: TranscribeOptions,
+    /// Limits how many transcriptions run at once, between requests and queued jobs.
+    permits: Semaphore,
+    max_upload_size: u64,
+    queue: Queue,
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    let mut whisper = args.model.load(None).await?;
+    if args.vad {
+        whisper = whisper.with_vad(EnergyVad::default());
+    }
+
+    let state = Arc::new(State {
+        whisper,
+        options: args.decoding.options(false, false),
+        permits: Semaphore::new(args.jobs.max(1)),
+        max_upload_size: args.max_upload_size * 1024 * 1024,
+        queue: Queue::open(args.queue_dir.map_or_else(default_queue_dir, Ok)?)?,
+    });
+
+    for _ in 0..args.jobs.max(1) {
+        tokio::spawn(work(state.clone()));
+    }
+
+    let service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    });
+
+    let addr = SocketAddr::new(args.host, args.port);
+    let server = hyper::Server::try_bind(&addr)?.serve(service);
+    eprintln!("Listening on http://{}", server.local_addr());
+
+    server
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Task {
+    Transcribe,
+    Translate,
+}
+
+async fn handle(state: Arc<State>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    let segments = path.split('/').skip(1).collect::<Vec<_>>();
+
+    let response = match (req.method(), segments.as_slice()) {
+        (&Method::POST, ["v1", "audio", "transcriptions"]) => {
+            transcribe(state, Task::Transcribe, req).await
+        }
+        (&Method::POST, ["v1", "audio", "translations"]) => {
+            transcribe(state, Task::Translate, req).await
+        }
+        (&Method::POST, ["v1", "audio", "transcriptions", "jobs"]) => {
+            submit(&state, Task::Transcribe, req).await
+        }
+        (&Method::POST, ["v1", "audio", "translations", "jobs"]) => {
+            submit(&state, Task::Translate, req).await
+        }
+        (&Method::GET, ["v1", "jobs"]) => Ok(json_response(
+            StatusCode::OK,
+            &json!({ "data": state.queue.list() }),
+        )),
+        (&Method::GET, ["v1", "jobs", id]) => job(&state, id),
+        (&Method::POST, ["v1", "jobs", id, "cancel"]) => cancel(&state, id),
+        (&Method::DELETE, ["v1", "jobs", id]) => remove(&state, id),
+        (&Method::GET, ["v1", "jobs", id, "result"]) => result(&state, id, req.uri().query()),
+        _ => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("no route for {} {path}", req.method()),
+        )),
+    };
+
+    Ok(response.unwrap_or_else(Response::from))
+}
+
+async fn transcribe(
+    state: Arc<State>,
+    task: Task,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let (body, boundary) = read_form(&state, req).await?;
+    let parts = parse_multipart(&body, &boundary)
+        .ok_or_else(|| ApiError::bad_request("malformed multipart body", None))?;
+    let request = TranscriptionRequest::new(&parts, task, &state)?;
+
+    // ffmpeg works out the format from the contents, but the extension helps it along
+    let extension = Path::new(request.file.filename.as_deref().unwrap_or_default())
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let mut file = tempfile::Builder::new()
+        .prefix("whisper-upload-")
+        .suffix(&extension)
+        .tempfile()
+        .map_err(ApiError::internal)?;
+    file.write_all(request.file.data)
+        .map_err(ApiError::internal)?;
+
+    let _permit = state
+        .permits
+        .acquire()
+        .await
+        .expect("the semaphore is never closed");
+
+    // stop transcribing if the client goes away, which drops this future
+    let token = CancellationToken::new();
+    let _guard = CancelOnDrop(token.clone());
+
+    let (lang, options, format) = (request.lang, request.options, request.format);
+    let shared = state.clone();
+    let (transcript, options) = tokio::task::spawn_blocking(move || {
+        let callbacks = Callbacks::default().cancel_with(token);
+        let result = run_transcription(&shared.whisper, file.path(), lang, &options, callbacks);
+        (result, options)
+    })
+    .await
+    .map_err(ApiError::internal)?;
+
+    let (transcript, duration) = match transcript {
+        Ok(transcription) => transcription,
+        Err(e @ (Error::Decode(_) | Error::InvalidToken(_))) => {
+            return Err(ApiError::bad_request(e.to_string(), None))
+        }
+        Err(e) => return Err(ApiError::internal(e)),
+    };
+
+    Ok(format.render(&transcript, &options, Some(duration)))
+}
+
+/// Transcribes `audio`, treating silence as an empty transcript (like OpenAI does) instead of an
+/// error. Returns the transcript along with how long the audio is, in seconds.
+fn run_transcription(
+    whisper: &Whisper,
+    audio: &Path,
+    lang: Option<Language>,
+    options: &TranscribeOptions,
+    callbacks: Callbacks<'_>,
+) -> whisper_cli::Result<(Transcript, f64)> {
+    let samples = decode_file(audio)?;
+    #[allow(clippy::cast_precision_loss)]
+    let duration = samples.len() as f64 / 16000.0;
+
+    let transcript = match whisper.transcribe_samples(&samples, lang, options, callbacks) {
+        Err(Error::NoSpeech) => Ok(Transcript {
+            language: lang,
+            model: None,
+            parameters: None,
+            utterances: Vec::new(),
+            word_utterances: None,
+            language_probabilities: None,
+            processing_time: Duration::ZERO,
+        }),
+        result => result,
+    }?;
+
+    Ok((transcript, duration))
+}
+
+/// Queues an upload to be transcribed in the background, responding with the new job.
+async fn submit(state: &State, task: Task, req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    let (body, boundary) = read_form(state, req).await?;
+    let parts = parse_multipart(&body, &boundary)
+        .ok_or_else(|| ApiError::bad_request("malformed multipart body", None))?;
+    let request = TranscriptionRequest::new(&parts, task, state)?;
+
+    let job = state
+        .queue
+        .submit(Submission {
+            audio: request.file.data,
+            language: request.lang,
+            options: request.options,
+            response_format: request.format,
+            file_name: request.file.filename.as_deref().unwrap_or("audio"),
+        })
+        .map_err(ApiError::internal)?;
+
+    Ok(json_response(StatusCode::ACCEPTED, &job))
+}
+
+fn job(state: &State, id: &str) -> Result<Response<Body>, ApiError> {
+    let job = state.queue.get(id).ok_or_else(|| job_not_found(id))?;
+    Ok(json_response(StatusCode::OK, &job))
+}
+
+fn cancel(state: &State, id: &str) -> Result<Response<Body>, ApiError> {
+    let job = state
+        .queue
+        .cancel(id)
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| job_not_found(id))?;
+
+    Ok(json_response(StatusCode::OK, &job))
+}
+
+fn remove(state: &State, id: &str) -> Result<Response<Body>, ApiError> {
+    if !state.queue.remove(id).map_err(ApiError::internal)? {
+        return Err(job_not_found(id));
+    }
+
+    Ok(json_response(
+        StatusCode::OK,
+        &json!({ "id": id, "deleted": true }),
+    ))
+}
+
+/// Renders the transcript of a completed job, in the format it was submitted with unless the
+/// `response_format` query parameter asks for another one. Besides the formats of the OpenAI
+/// API, that can be any format transcripts can be saved as from the command line, like `ass`.
+fn result(state: &State, id: &str, query: Option<&str>) -> Result<Response<Body>, ApiError> {
+    let job = state.queue.get(id).ok_or_else(|| job_not_found(id))?;
+    if job.status != Status::Completed {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("job {id} is {}", job.status),
+        ));
+    }
+
+    let requested = query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("response_format="));
+    let transcript = state.queue.transcript(id).map_err(ApiError::internal)?;
+    Ok(match requested {
+        None => job
+            .response_format
+            .render(&transcript, &job.options, job.duration),
+        Some(name) => match (ResponseFormat::parse(name), OutputFormat::from_name(name)) {
+            (Some(format), _) => format.render(&transcript, &job.options, job.duration),
+            (None, Some(format)) => file_response(format, &transcript),
+            (None, None) => {
+                return Err(ApiError::bad_request(
+                    format!("unsupported response_format {name}"),
+                    Some("response_format"),
+                ))
+            }
+        },
+    })
+}
+
+fn job_not_found(id: &str) -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, format!("no job with id {id}"))
+}
+
+/// Cancels the token when dropped.
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Transcribes queued jobs one after another, for as long as the server runs.
+async fn work(state: Arc<State>) {
+    loop {
+        let id = state.queue.next().await;
+        let _permit = state
+            .permits
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+
+        let (job, token) = match state.queue.start(&id) {
+            Ok(Some(started)) => started,
+            // cancelled while it was waiting
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("Error: {id}: {e}");
+                continue;
+            }
+        };
+
+        let shared = state.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let audio = job.audio_path(&shared.queue);
+            let callbacks = Callbacks::default()
+                .cancel_with(token)
+                .on_progress(|progress| shared.queue.set_progress(&job.id, progress));
+
+            run_transcription(
+                &shared.whisper,
+                &audio,
+                job.language,
+                &job.options,
+                callbacks,
+            )
+            .map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+        if let Err(e) = state.queue.finish(&id, result) {
+            eprintln!("Error: {id}: {e}");
+        }
+    }
+}
+
+/// The fields of a transcription request, in the shape of the OpenAI audio API.
+struct TranscriptionRequest<'a> {
+    file: Part<'a>,
+    lang: Option<Language>,
+    format: ResponseFormat,
+    options: TranscribeOptions,
+}
+
+impl<'a> TranscriptionRequest<'a> {
+    fn new(parts: &[Part<'a>], task: Task, state: &State) -> Result<Self, ApiError> {
+        let field = |name: &str| {
+            parts
+                .iter()
+                .find(|part| part.name == name)
+                .map(|part| String::from_utf8_lossy(part.data).trim().to_string())
+        };
+
+        let file = parts
+            .iter()
+            .find(|part| part.name == "file")
+            .cloned()
+            .ok_or_else(|| ApiError::bad_request("missing audio file", Some("file")))?;
+
+        let format = field("response_format").map_or(Ok(ResponseFormat::Json), |format| {
+            ResponseFormat::parse(&format).ok_or_else(|| {
+                ApiError::bad_request(
+                    format!("unsupported response_format {format}"),
+                    Some("response_format"),
+                )
+            })
+        })?;
+
+        // translations are always to English, so the source language is left to detection
+        let lang = match (task, field("language").filter(|code| !code.is_empty())) {
+            (Task::Transcribe, Some(code)) => {
+                Some(Language::from_code(&code).ok_or_else(|| {
+                    ApiError::bad_request(format!("unknown language {code}"), Some("language"))
+                })?)
+            }
+            _ => None,
+        };
+        if !state.whisper.is_multilingual() && lang.is_some_and(|lang| lang != Language::English) {
+            return Err(ApiError::bad_request(
+                "the model only supports English",
+                Some("language"),
+            ));
+        }
+
+        let mut options = state.options.clone();
+        options.translate = matches!(task, Task::Translate);
+        options.word_timestamps = format == ResponseFormat::VerboseJson
+            && parts
+                .iter()
+                .filter(|part| part.name == "timestamp_granularities[]")
+                .any(|part| part.data == b"word");
+
+        if let Some(prompt) = field("prompt").filter(|prompt| !prompt.is_empty()) {
+            options.initial_prompt = Some(prompt);
+        }
+
+        if let Some(temperature) = field("temperature") {
+            options.temperature = temperature.parse().map_err(|_| {
+                ApiError::bad_request(
+                    format!("invalid temperature {temperature}"),
+                    Some("temperature"),
+                )
+            })?;
+        }
+
+        Ok(Self {
+            file,
+            lang,
+            format,
+            options,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Json,
+    Text,
+    Srt,
+    Vtt,
+    VerboseJson,
+}
+
+impl ResponseFormat {
+    fn parse(format: &str) -> Option<Self> {
+        match format {
+            "json" => Some(Self::Json),
+            "text" => Some(Self::Text),
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::Vtt),
+            "verbose_json" => Some(Self::VerboseJson),
+            _ => None,
+        }
+    }
+
+    /// Renders `transcript` as a response. `duration` is how long the audio is, in seconds, when
+    /// it's known.
+    fn render(
+        self,
+        transcript: &Transcript,
+        options: &TranscribeOptions,
+        duration: Option<f64>,
+    ) -> Response<Body> {
+        let text = transcript
+            .utterances
+            .iter()
+            .map(|utterance| utterance.text.as_str())
+            .collect::<String>()
+            .trim()
+            .to_string();
+
+        let (content_type, body) = match self {
+            Self::Json => ("application/json", json!({ "text": text }).to_string()),
+            Self::Text => ("text/plain; charset=utf-8", format!("{text}\n")),
+            Self::Srt => ("text/plain; charset=utf-8", transcript.as_srt()),
+            Self::Vtt => ("text/vtt; charset=utf-8", transcript.as_vtt()),
+            Self::VerboseJson => (
+                "application/json",
+                serde_json::to_string(&Verbose::new(transcript, text, options, duration))
+                    .expect("transcripts should always serialize"),
+            ),
+        };
+
+        Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .expect("the response should be valid")
+    }
+}
+
+/// The `verbose_json` response format.
+#[derive(Serialize)]
+struct Verbose<'a> {
+    task: &'static str,
+    language: Option<String>,
+    duration: f64,
+    text: String,
+    segments: Vec<Segment<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<Vec<Word<'a>>>,
+}
+
+#[derive(Serialize)]
+struct Segment<'a> {
+    id: usize,
+    seek: i64,
+    start: f64,
+    end: f64,
+    text: &'a str,
+    tokens: Vec<i32>,
+    temperature: f32,
+    avg_logprob: f32,
+    compression_ratio: f32,
+    no_speech_prob: f32,
+}
+
+#[derive(Serialize)]
+#[allow(clippy::struct_field_names)]
+struct Word<'a> {
+    word: &'a str,
+    start: f64,
+    end: f64,
+}
+
+impl<'a> Verbose<'a> {
+    fn new(
+        transcript: &'a Transcript,
+        text: String,
+        options: &TranscribeOptions,
+        duration: Option<f64>,
+    ) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        let seconds = |ticks: i64| ticks as f64 / 100.0;
+
+        let segments = transcript
+            .utterances
+            .iter()
+            .enumerate()
+            .map(|(id, utterance)| {
+                #[allow(clippy::cast_precision_loss)]
+                let avg_logprob = utterance.tokens.iter().map(|token| token.plog).sum::<f32>()
+                    / utterance.tokens.len().max(1) as f32;
+
+                Segment {
+                    id,
+                    avg_logprob,
+                    compression_ratio: compression_ratio(&utterance.text),
+                    // whisper-rs only reads whisper.cpp's no-speech probability from the
+                    // context's own state, not the ones transcriptions run on, and whisper drops
+                    // segments it thinks aren't speech
+                    no_speech_prob: 0.0,
+                    // whisper.cpp doesn't report where each window started, and frames are as
+                    // long as ticks, so segments seek to where they start
+                    seek: utterance.start,
+                    start: seconds(utterance.start),
+                    end: seconds(utterance.stop),
+                    text: &utterance.text,
+                    tokens: utterance.tokens.iter().map(|token| token.id).collect(),
+                    temperature: options.temperature,
+                }
+            })
+            .collect();
+
+        let words = transcript.word_utterances.as_ref().map(|words| {
+            words
+                .iter()
+                .map(|word| Word {
+                    word: word.text.trim(),
+                    start: seconds(word.start),
+                    end: seconds(word.stop),
+                })
+                .collect()
+        });
+
+        Self {
+            text,
+            words,
+            segments,
+            task: if options.translate {
+                "translate"
+            } else {
+                "transcribe"
+            },
+            language: transcript
+                .language
+                .and_then(language_name)
+                .map(str::to_string),
+            // jobs that finished before their duration was recorded only go as far as the speech
+            duration: duration.unwrap_or_else(|| {
+                seconds(
+                    transcript
+                        .utterances
+                        .last()
+                        .map_or(0, |utterance| utterance.stop),
+                )
+            }),
+        }
+    }
+}
+
+/// The name the OpenAI API gives `lang`, like `english` or `haitian creole`.
+#[allow(clippy::too_many_lines)]
+const fn language_name(lang: Language) -> Option<&'static str> {
+    Some(match lang {
+        Language::Auto => return None,
+        Language::English => "english",
+        Language::Chinese => "chinese",
+        Language::German => "german",
+        Language::Spanish => "spanish",
+        Language::Russian => "russian",
+        Language::Korean => "korean",
+        Language::French => "french",
+        Language::Japanese => "japanese",
+        Language::Portuguese => "portuguese",
+        Language::Turkish => "turkish",
+        Language::Polish => "polish",
+        Language::Catalan => "catalan",
+        Language::Dutch => "dutch",
+        Language::Arabic => "arabic",
+        Language::Swedish => "swedish",
+        Language::Italian => "italian",
+        Language::Indonesian => "indonesian",
+        Language::Hindi => "hindi",
+        Language::Finnish => "finnish",
+        Language::Vietnamese => "vietnamese",
+        Language::Hebrew => "hebrew",
+        Language::Ukrainian => "ukrainian",
+        Language::Greek => "greek",
+        Language::Malay => "malay",
+        Language::Czech => "czech",
+        Language::Romanian => "romanian",
+        Language::Danish => "danish",
+        Language::Hungarian => "hungarian",
+        Language::Tamil => "tamil",
+        Language::Norwegian => "norwegian",
+        Language::Thai => "thai",
+        Language::Urdu => "urdu",
+        Language::Croatian => "croatian",
+        Language::Bulgarian => "bulgarian",
+        Language::Lithuanian => "lithuanian",
+        Language::Latin => "latin",
+        Language::Maori => "maori",
+        Language::Malayalam => "malayalam",
+        Language::Welsh => "welsh",
+        Language::Slovak => "slovak",
+        Language::Telugu => "telugu",
+        Language::Persian => "persian",
+        Language::Latvian => "latvian",
+        Language::Bengali => "bengali",
+        Language::Serbian => "serbian",
+        Language::Azerbaijani => "azerbaijani",
+        Language::Slovenian => "slovenian",
+        Language::Kannada => "kannada",
+        Language::Estonian => "estonian",
+        Language::Macedonian => "macedonian",
+        Language::Breton => "breton",
+        Language::Basque => "basque",
+        Language::Icelandic => "icelandic",
+        Language::Armenian => "armenian",
+        Language::Nepali => "nepali",
+        Language::Mongolian => "mongolian",
+        Language::Bosnian => "bosnian",
+        Language::Kazakh => "kazakh",
+        Language::Albanian => "albanian",
+        Language::Swahili => "swahili",
+        Language::Galician => "galician",
+        Language::Marathi => "marathi",
+        Language::Punjabi => "punjabi",
+        Language::Sinhala => "sinhala",
+        Language::Khmer => "khmer",
+        Language::Shona => "shona",
+        Language::Yoruba => "yoruba",
+        Language::Somali => "somali",
+        Language::Afrikaans => "afrikaans",
+        Language::Occitan => "occitan",
+        Language::Georgian => "georgian",
+        Language::Belarusian => "belarusian",
+        Language::Tajik => "tajik",
+        Language::Sindhi => "sindhi",
+        Language::Gujarati => "gujarati",
+        Language::Amharic => "amharic",
+        Language::Yiddish => "yiddish",
+        Language::Lao => "lao",
+        Language::Uzbek => "uzbek",
+        Language::Faroese => "faroese",
+        Language::HaitianCreole => "haitian creole",
+        Language::Pashto => "pashto",
+        Language::Turkmen => "turkmen",
+        Language::Nynorsk => "nynorsk",
+        Language::Maltese => "maltese",
+        Language::Sanskrit => "sanskrit",
+        Language::Luxembourgish => "luxembourgish",
+        Language::Myanmar => "myanmar",
+        Language::Tibetan => "tibetan",
+        Language::Tagalog => "tagalog",
+        Language::Malagasy => "malagasy",
+        Language::Assamese => "assamese",
+        Language::Tatar => "tatar",
+        Language::Hawaiian => "hawaiian",
+        Language::Lingala => "lingala",
+        Language::Hausa => "hausa",
+        Language::Bashkir => "bashkir",
+        Language::Javanese => "javanese",
+        Language::Sundanese => "sundanese",
+    })
+}
+
+/// How much `text` shrinks when compressed with zlib, which is how the API flags repetitive text.
+#[allow(clippy::cast_precision_loss)]
+fn compression_ratio(text: &str) -> f32 {
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(text.as_bytes(), 6);
+    text.len() as f32 / compressed.len() as f32
+}
+
+/// An error, in the shape the OpenAI API reports them.
+struct ApiError {
+    status: StatusCode,
+    message: String,
+    param: Option<&'static str>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            param: None,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>, param: Option<&'static str>) -> Self {
+        Self {
+            param,
+            ..Self::new(StatusCode::BAD_REQUEST, message)
+        }
+    }
+
+    fn internal(error: impl std::fmt::Display) -> Self {
+        eprintln!("Error: {error}");
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+    }
+}
+
+impl From<ApiError> for Response<Body> {
+    fn from(error: ApiError) -> Self {
+        let kind = if error.status.is_server_error() {
+            "server_error"
+        } else {
+            "invalid_request_error"
+        };
+
+        let body = json!({
+            "error": {
+                "message": error.message,
+                "type": kind,
+                "param": error.param,
+                "code": null,
+            }
+        });
+
+        json_response(error.status, &body)
+    }
+}
+
+/// Reads a `multipart/form-data` request, returning its body and boundary.
+async fn read_form(state: &State, req: Request<Body>) -> Result<(Vec<u8>, String), ApiError> {
+    let boundary = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(boundary)
+        .ok_or_else(|| ApiError::bad_request("expected a multipart/form-data body", None))?;
+
+    Ok((read_body(req, state.max_upload_size).await?, boundary))
+}
+
+/// A transcript rendered the way it's saved from the command line.
+fn file_response(format: OutputFormat, transcript: &Transcript) -> Response<Body> {
+    let content_type = match format.name() {
+        "json" => "application/json",
+        "jsonl" => "application/x-ndjson",
+        "vtt" => "text/vtt; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "tsv" => "text/tab-separated-values; charset=utf-8",
+        "ttml" => "application/ttml+xml",
+        _ => "text/plain; charset=utf-8",
+    };
+
+    Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(format.render(transcript)))
+        .expect("the response should be valid")
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::to_string(body).expect("responses should always serialize"),
+        ))
+        .expect("the response should be valid")
+}
+
+/// Reads the whole request body, rejecting it once it gets larger than `limit` bytes.
+async fn read_body(req: Request<Body>, limit: u64) -> Result<Vec<u8>, ApiError> {
+    let too_large = || {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("uploads are limited to {}", indicatif::HumanBytes(limit)),
+        )
+    };
+
+    let length = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if length.is_some_and(|length| length > limit) {
+        return Err(too_large());
+    }
+
+    let mut body = req.into_body();
+    let mut bytes =
+        Vec::with_capacity(length.map_or(0, |length| usize::try_from(length).unwrap_or_default()));
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| ApiError::bad_request(e.to_string(), None))?;
+        if (bytes.len() + chunk.len()) as u64 > limit {
+            return Err(too_large());
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+/// The boundary of a `multipart/form-data` content type.
+fn boundary(content_type: &str) -> Option<String> {
+    let media_type = content_type.split(';').next()?.trim();
+    if !media_type.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    header_param(content_type, "boundary")
+}
+
+/// Looks up a parameter of a header value like `form-data; name="file"; filename="a;b.mp3"`,
+/// reading quoted values (which can have `;` in them) up to their closing quote.
+fn header_param(value: &str, key: &str) -> Option<String> {
+    // the parameters come after the value itself
+    let mut rest = value.split_once(';')?.1;
+    while !rest.is_empty() {
+        let key_end = rest.find(['=', ';']).unwrap_or(rest.len());
+        let name = rest[..key_end].trim();
+        rest = &rest[key_end..];
+
+        let value = match rest.strip_prefix('=').map(str::trim_start) {
+            Some(quoted) if quoted.starts_with('"') => {
+                let (value, after) = unquote(&quoted[1..]);
+                rest = after;
+                Some(value)
+            }
+            Some(token) => {
+                let end = token.find(';').unwrap_or(token.len());
+                rest = &token[end..];
+                Some(token[..end].trim().to_string())
+            }
+            // a parameter without a value
+            None => None,
+        };
+
+        if name.eq_ignore_ascii_case(key) {
+            return value;
+        }
+        rest = rest.find(';').map_or("", |i| &rest[i + 1..]);
+    }
+
+    None
+}
+
+/// Reads a quoted string up to its closing quote (with the opening one already stripped), undoing
+/// `\` escapes. Returns its contents and what follows the closing quote.
+fn unquote(quoted: &str) -> (String, &str) {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return (value, &quoted[i + 1..]),
+            '\\' => value.extend(chars.next().map(|(_, c)| c)),
+            c => value.push(c),
+        }
+    }
+
+    // an unterminated string runs to the end of the value
+    (value, "")
+}
+
+/// A field of a `multipart/form-data` body.
+#[derive(Clone)]
+struct Part<'a> {
+    name: String,
+    filename: Option<String>,
+    data: &'a [u8],
+}
+
+/// Splits a `multipart/form-data` body into its fields, as described in RFC 7578.
+fn parse_multipart<'a>(body: &'a [u8], boundary: &str) -> Option<Vec<Part<'a>>> {
+    let delimiter = format!("--{boundary}");
+    let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+    let next_delimiter = format!("\r\n{delimiter}");
+
+    let mut parts = Vec::new();
+    // the last delimiter is followed by `--`
+    while !rest.starts_with(b"--") {
+        rest = rest.strip_prefix(b"\r\n")?;
+
+        let headers_end = find(rest, b"\r\n\r\n")?;
+        let headers = std::str::from_utf8(&rest[..headers_end]).ok()?;
+        rest = &rest[headers_end + 4..];
+
+        let data_end = find(rest, next_delimiter.as_bytes())?;
+        let data = &rest[..data_end];
+        rest = &rest[data_end + next_delimiter.len()..];
+
+        let disposition = headers.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("content-disposition")
+                .then_some(value)
+        })?;
+
+        parts.push(Part {
+            data,
+            name: header_param(disposition, "name")?,
+            filename: header_param(disposition, "filename"),
+        });
+    }
+
+    Some(parts)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_boundary() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=abc123").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; Boundary=\"a b;c\"").as_deref(),
+            Some("a b;c")
+        );
+        assert_eq!(boundary("application/json; boundary=abc"), None);
+        assert_eq!(boundary("multipart/form-data"), None);
+    }
+
+    #[test]
+    fn reads_quoted_parameters() {
+        let disposition = r#"form-data; name="file"; filename="a;b \"c\".mp3""#;
+        assert_eq!(header_param(disposition, "name").as_deref(), Some("file"));
+        assert_eq!(
+            header_param(disposition, "filename").as_deref(),
+            Some(r#"a;b "c".mp3"#)
+        );
+        assert_eq!(
+            header_param("form-data; name=model", "name").as_deref(),
+            Some("model")
+        );
+        assert_eq!(header_param("form-data; name=\"model\"", "filename"), None);
+    }
+
+    #[test]
+    fn splits_fields_and_files() {
+        let body = b"preamble\r\n--xyz\r\n\
+            Content-Disposition: form-data; name=\"model\"\r\n\r\n\
+            whisper-1\r\n--xyz\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a;b.mp3\"\r\n\
+            Content-Type: audio/mpeg\r\n\r\n\
+            ID3\r\n\x00\xff\r\n--xyz--\r\n";
+        let parts = parse_multipart(body, "xyz").unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "model");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"whisper-1");
+        assert_eq!(parts[1].name, "file");
+        assert_eq!(parts[1].filename.as_deref(), Some("a;b.mp3"));
+        assert_eq!(parts[1].data, b"ID3\r\n\x00\xff");
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        // no closing delimiter
+        let body = b"--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue";
+        assert!(parse_multipart(body, "xyz").is_none());
+        // no name
+        let body = b"--xyz\r\nContent-Disposition: form-data\r\n\r\nvalue\r\n--xyz--";
+        assert!(parse_multipart(body, "xyz").is_none());
+    }
+}
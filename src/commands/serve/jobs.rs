@@ -0,0 +1,405 @@
+use super::ResponseFormat;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Notify;
+use whisper_cli::{CancellationToken, Language, TranscribeOptions, Transcript};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        };
+
+        write!(f, "{key}")
+    }
+}
+
+impl Status {
+    const fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// A transcription that runs in the background, stored in `job.json` in its own directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub status: Status,
+    /// How far along the transcription is, from 0 to 1.
+    pub progress: f32,
+    /// Name of the uploaded file.
+    pub file_name: String,
+    /// Language spoken in the audio, detected if missing.
+    pub language: Option<Language>,
+    /// Format the result is returned in, unless another one is requested when fetching it.
+    pub response_format: ResponseFormat,
+    pub options: TranscribeOptions,
+    /// Unix timestamps, in seconds.
+    pub created_at: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    /// Length of the audio, in seconds. Only known once the job has completed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Job {
+    /// Where the uploaded audio is kept until the job finishes.
+    pub fn audio_path(&self, queue: &Queue) -> PathBuf {
+        let extension = Path::new(&self.file_name)
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+
+        queue.dir.join(&self.id).join(format!("audio{extension}"))
+    }
+}
+
+/// What [`Queue::submit`] needs to create a job.
+pub struct Submission<'a> {
+    pub file_name: &'a str,
+    pub audio: &'a [u8],
+    pub language: Option<Language>,
+    pub response_format: ResponseFormat,
+    pub options: TranscribeOptions,
+}
+
+/// A queue of transcription jobs, persisted to a directory so that jobs survive restarts.
+pub struct Queue {
+    dir: PathBuf,
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct State {
+    jobs: BTreeMap<String, Job>,
+    /// Ids of the queued jobs, oldest first.
+    pending: VecDeque<String>,
+    /// Tokens to cancel the running jobs with.
+    running: BTreeMap<String, CancellationToken>,
+}
+
+impl Queue {
+    /// Opens the queue in `dir`, picking up any jobs that hadn't finished when the server last
+    /// stopped. Jobs that were running are started over.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let mut state = State::default();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let path = entry.path().join("job.json");
+            let contents = match fs::read(&path) {
+                Ok(contents) => contents,
+                // directories of jobs that were being created or deleted
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            // one unreadable record shouldn't take every other job down with it
+            let mut job: Job = match serde_json::from_slice(&contents) {
+                Ok(job) => job,
+                Err(e) => {
+                    eprintln!("Warning: skipping job {}: {e}", path.display());
+                    continue;
+                }
+            };
+
+            if job.status == Status::Running {
+                job.status = Status::Queued;
+                job.progress = 0.0;
+                job.started_at = None;
+                save(&path, &job)?;
+            }
+
+            state.jobs.insert(job.id.clone(), job);
+        }
+
+        let mut pending = state
+            .jobs
+            .values()
+            .filter(|job| job.status == Status::Queued)
+            .map(|job| (job.created_at, job.id.clone()))
+            .collect::<Vec<_>>();
+        pending.sort();
+        state.pending = pending.into_iter().map(|(_, id)| id).collect();
+
+        Ok(Self {
+            dir,
+            state: Mutex::new(state),
+            notify: Notify::new(),
+        })
+    }
+
+    pub fn submit(&self, submission: Submission) -> io::Result<Job> {
+        let job = Job {
+            id: new_id(),
+            progress: 0.0,
+            error: None,
+            started_at: None,
+            finished_at: None,
+            duration: None,
+            created_at: now(),
+            status: Status::Queued,
+            language: submission.language,
+            options: submission.options,
+            file_name: submission.file_name.to_string(),
+            response_format: submission.response_format,
+        };
+
+        fs::create_dir_all(self.dir.join(&job.id))?;
+        fs::write(job.audio_path(self), submission.audio)?;
+        self.save(&job)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.jobs.insert(job.id.clone(), job.clone());
+        state.pending.push_back(job.id.clone());
+        drop(state);
+
+        self.notify.notify_one();
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.state.lock().unwrap().jobs.get(id).cloned()
+    }
+
+    /// Every job, oldest first.
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs = self
+            .state
+            .lock()
+            .unwrap()
+            .jobs
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        jobs.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+
+        jobs
+    }
+
+    /// Waits for a job to be queued, returning its id. The job isn't started until
+    /// [`Queue::start`] is called, so it can still be cancelled in the meantime.
+    pub async fn next(&self) -> String {
+        loop {
+            let next = self.state.lock().unwrap().pending.pop_front();
+            if let Some(id) = next {
+                return id;
+            }
+
+            self.notify.notified().await;
+        }
+    }
+
+    /// Marks a job as running, unless it has been cancelled since it was queued. Returns the job
+    /// along with the token that gets cancelled if the job is.
+    pub fn start(&self, id: &str) -> io::Result<Option<(Job, CancellationToken)>> {
+        let job = self.update(id, |job| {
+            if job.status != Status::Queued {
+                return false;
+            }
+
+            job.status = Status::Running;
+            job.started_at = Some(now());
+            true
+        })?;
+
+        Ok(job.map(|job| {
+            let token = CancellationToken::new();
+            self.state
+                .lock()
+                .unwrap()
+                .running
+                .insert(job.id.clone(), token.clone());
+
+            (job, token)
+        }))
+    }
+
+    /// Updates how far along a running job is. Progress isn't saved to disk, since running jobs
+    /// start over after a restart anyway.
+    pub fn set_progress(&self, id: &str, progress: f32) {
+        if let Some(job) = self.state.lock().unwrap().jobs.get_mut(id) {
+            job.progress = progress;
+        }
+    }
+
+    /// Records the result of a running job, and deletes its audio.
+    pub fn finish(&self, id: &str, result: Result<(Transcript, f64), String>) -> io::Result<()> {
+        self.state.lock().unwrap().running.remove(id);
+
+        // the job was deleted while it was running
+        let Some(job) = self.get(id) else {
+            return Ok(());
+        };
+
+        let transcript_path = self.dir.join(id).join("transcript.json");
+        self.update(id, |job| {
+            // the job was cancelled while it was running, so its result isn't wanted anymore
+            if job.status != Status::Running {
+                return false;
+            }
+
+            let result = result.and_then(|(transcript, duration)| {
+                fs::write(&transcript_path, transcript.as_json())
+                    .map(|()| duration)
+                    .map_err(|e| format!("failed to save transcript: {e}"))
+            });
+
+            job.finished_at = Some(now());
+            match result {
+                Ok(duration) => {
+                    job.duration = Some(duration);
+                    job.progress = 1.0;
+                    job.status = Status::Completed;
+                }
+                Err(error) => {
+                    job.status = Status::Failed;
+                    job.error = Some(error);
+                }
+            }
+            true
+        })?;
+
+        remove_file(&job.audio_path(self))
+    }
+
+    /// Cancels a job that hasn't finished yet. Returns the job, or `None` if there's no such job.
+    pub fn cancel(&self, id: &str) -> io::Result<Option<Job>> {
+        if let Some(token) = self.state.lock().unwrap().running.get(id) {
+            token.cancel();
+        }
+
+        self.update(id, |job| {
+            if job.status.is_finished() {
+                return false;
+            }
+
+            job.status = Status::Cancelled;
+            job.finished_at = Some(now());
+            true
+        })?;
+
+        let Some(job) = self.get(id) else {
+            return Ok(None);
+        };
+        remove_file(&job.audio_path(self))?;
+
+        Ok(Some(job))
+    }
+
+    /// Deletes a job and everything stored for it, cancelling it first if it hasn't finished.
+    /// Returns whether there was such a job.
+    pub fn remove(&self, id: &str) -> io::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        if state.jobs.remove(id).is_none() {
+            return Ok(false);
+        }
+        state.pending.retain(|pending| pending != id);
+        if let Some(token) = state.running.remove(id) {
+            token.cancel();
+        }
+        drop(state);
+
+        // remove the record first, so a half-deleted job isn't picked up again on restart
+        remove_file(&self.dir.join(id).join("job.json"))?;
+        fs::remove_dir_all(self.dir.join(id))?;
+
+        Ok(true)
+    }
+
+    /// The transcript of a completed job.
+    pub fn transcript(&self, id: &str) -> io::Result<Transcript> {
+        let contents = fs::read(self.dir.join(id).join("transcript.json"))?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    /// Applies `change` to a job and saves it, if `change` returns true. Returns the job, or `None`
+    /// if there's no job with that id.
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job) -> bool) -> io::Result<Option<Job>> {
+        let mut state = self.state.lock().unwrap();
+        let Some(job) = state.jobs.get_mut(id) else {
+            return Ok(None);
+        };
+
+        if !change(job) {
+            return Ok(None);
+        }
+
+        let job = job.clone();
+        if job.status != Status::Queued {
+            state.pending.retain(|pending| pending != id);
+        }
+        drop(state);
+
+        self.save(&job)?;
+        Ok(Some(job))
+    }
+
+    fn save(&self, job: &Job) -> io::Result<()> {
+        save(&self.dir.join(&job.id).join("job.json"), job)
+    }
+}
+
+/// Writes the job to a temporary file first, so a crash never leaves a truncated record behind.
+fn save(path: &Path, job: &Job) -> io::Result<()> {
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_vec_pretty(job)?)?;
+    fs::rename(partial, path)
+}
+
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// A unique, roughly time-ordered job id.
+fn new_id() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed) & 0xffff;
+
+    format!("job_{nanos:x}{count:04x}")
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+use whisper_cli::{Alignment, AssOptions, AssStyle, AssStyling, Colour, SubtitleLayout};
+
+#[derive(clap::Args)]
+#[command(next_help_heading = "Subtitles")]
+pub struct Args {
+    /// Maximum number of characters per subtitle line
+    #[clap(long, default_value_t = SubtitleLayout::default().max_line_length)]
+    max_line_length: usize,
+
+    /// Maximum number of lines per subtitle
+    #[clap(long, default_value_t = SubtitleLayout::default().max_lines)]
+    max_lines: usize,
+
+    /// Maximum time a subtitle stays on screen, in seconds
+    #[clap(long, default_value_t = SubtitleLayout::default().max_duration.as_secs_f32(), value_parser = positive_seconds)]
+    max_duration: f32,
+
+    /// Minimum gap between subtitles, in milliseconds
+    #[clap(long, default_value_t = SubtitleLayout::default().min_gap.as_millis())]
+    min_gap: u128,
+
+    /// Maximum reading speed, in characters per second. Subtitles are kept on screen longer to match.
+    #[clap(long, default_value_t = SubtitleLayout::default().max_cps, value_parser = positive)]
+    max_cps: f32,
+
+    /// Use whisper's segments as subtitles as they are, instead of splitting them up
+    #[clap(long, default_value = "false")]
+    keep_segments: bool,
+
+    /// Font of ASS subtitles
+    #[clap(long, default_value_t = AssStyle::default().font)]
+    font: String,
+
+    /// Font size of ASS subtitles, in pixels of a 1080p video
+    #[clap(long, default_value_t = AssStyle::default().font_size)]
+    font_size: u32,
+
+    /// Use a bold font for ASS subtitles
+    #[clap(long, default_value = "false")]
+    bold: bool,
+
+    /// Text colour of ASS subtitles, as #RRGGBB or #RRGGBBAA
+    #[clap(long, default_value_t = AssStyle::default().colour)]
+    colour: Colour,
+
+    /// Colour of the words that haven't been spoken yet in ASS subtitles, with --karaoke
+    #[clap(long, default_value_t = AssStyle::default().karaoke_colour)]
+    karaoke_colour: Colour,
+
+    /// Colour of the outline around the text of ASS subtitles
+    #[clap(long, default_value_t = AssStyle::default().outline_colour)]
+    outline_colour: Colour,
+
+    /// Where ASS subtitles are placed on the video
+    #[clap(long, default_value = "bottom")]
+    alignment: Alignment,
+
+    /// Distance of ASS subtitles from the left and right edges of the video, in pixels of a 1080p video
+    #[clap(long, default_value_t = AssStyle::default().margin_left)]
+    margin_horizontal: u32,
+
+    /// Distance of ASS subtitles from the top or bottom edge of the video, in pixels of a 1080p video
+    #[clap(long, default_value_t = AssStyle::default().margin_vertical)]
+    margin_vertical: u32,
+
+    /// Which lines of ASS subtitles get a style of their own
+    #[clap(long, default_value = "single")]
+    ass_styles: AssStyling,
+}
+
+impl Args {
+    /// The layout to render subtitles with, if they should be laid out at all.
+    pub fn layout(&self) -> Option<SubtitleLayout> {
+        if self.keep_segments {
+            return None;
+        }
+
+        Some(SubtitleLayout {
+            max_cps: self.max_cps,
+            max_lines: self.max_lines.max(1),
+            max_line_length: self.max_line_length,
+            min_gap: Duration::from_millis(u64::try_from(self.min_gap).unwrap_or(u64::MAX)),
+            max_duration: Duration::from_secs_f32(self.max_duration),
+        })
+    }
+
+    pub fn ass_options(&self) -> AssOptions {
+        AssOptions {
+            styling: self.ass_styles,
+            style: AssStyle {
+                bold: self.bold,
+                font: self.font.clone(),
+                font_size: self.font_size,
+                colour: self.colour,
+                karaoke_colour: self.karaoke_colour,
+                outline_colour: self.outline_colour,
+                alignment: self.alignment,
+                margin_left: self.margin_horizontal,
+                margin_right: self.margin_horizontal,
+                margin_vertical: self.margin_vertical,
+                ..AssStyle::default()
+            },
+        }
+    }
+}
+
+/// Parses a finite number above zero, for limits that make no sense at zero or below.
+fn positive(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+        Ok(_) => Err("has to be above 0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses a number of seconds above zero, short enough to fit in a [`Duration`].
+fn positive_seconds(value: &str) -> Result<f32, String> {
+    let seconds = positive(value)?;
+    Duration::try_from_secs_f32(seconds).map_err(|e| e.to_string())?;
+
+    Ok(seconds)
+}
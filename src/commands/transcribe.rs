@@ -0,0 +1,249 @@
+use super::{decoding, output};
+use crate::ModelArgs;
+use anyhow::{anyhow, Result};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use whisper_cli::{
+    decode_file, diarize, diarize_by_voice, Callbacks, Diarization, EnergyVad, Language, Whisper,
+};
+
+/// Extensions picked up when searching a directory for audio files.
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "3gp", "aac", "aif", "aiff", "amr", "avi", "flac", "m4a", "mkv", "mov", "mp3", "mp4", "oga",
+    "ogg", "opus", "wav", "webm", "wma",
+];
+
+#[derive(clap::Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Args {
+    #[clap(flatten)]
+    model: ModelArgs,
+
+    /// Language spoken in the audio. Attempts to auto-detect by default.
+    #[clap(short, long)]
+    lang: Option<Language>,
+
+    /// Audio files, directories or glob patterns to transcribe
+    #[clap(required = true)]
+    audio: Vec<String>,
+
+    /// Toggle translation
+    #[clap(short, long, default_value = "false")]
+    translate: bool,
+
+    /// Generate timestamps for each word
+    #[clap(short, long, default_value = "false")]
+    karaoke: bool,
+
+    /// Align words with the audio using DTW, which is slower but more accurate. Needs one of the
+    /// standard (not distilled) models.
+    #[clap(long, requires = "karaoke")]
+    dtw: bool,
+
+    /// Skip silent parts of the audio instead of transcribing them
+    #[clap(long, default_value = "false")]
+    vad: bool,
+
+    /// Label who is speaking in each utterance
+    #[clap(long, value_name = "METHOD")]
+    diarize: Option<DiarizeMethod>,
+
+    /// Number of speakers in the audio, when diarizing by clustering. Estimated by default.
+    #[clap(long, requires = "diarize")]
+    speakers: Option<usize>,
+
+    /// Number of files to transcribe concurrently
+    #[clap(short, long, default_value = "1")]
+    jobs: usize,
+
+    #[clap(flatten)]
+    decoding: decoding::Args,
+
+    #[clap(flatten)]
+    output: output::Args,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum DiarizeMethod {
+    /// Each speaker was recorded on their own channel of a stereo file
+    Stereo,
+    /// Group utterances by how similar the voices sound
+    Cluster,
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    let files = collect_files(&args.audio)?;
+    args.output.check_collisions(&files)?;
+    let mut whisper = args.model.load_with(args.lang, args.dtw).await?;
+    if args.vad {
+        whisper = whisper.with_vad(EnergyVad::default());
+    }
+
+    let queue = Mutex::new(files.iter());
+    let failures = Mutex::new(Vec::new());
+    let skipped = AtomicUsize::new(0);
+    let progress = MultiProgress::new();
+
+    thread::scope(|scope| {
+        for _ in 0..args.jobs.clamp(1, files.len()) {
+            scope.spawn(|| loop {
+                let Some(audio) = queue.lock().unwrap().next() else {
+                    break;
+                };
+
+                match transcribe_file(&whisper, audio, &args, &progress) {
+                    Ok(Some(time)) => progress.suspend(|| {
+                        status(&args, &format!("{}: done in {time:?}", audio.display()));
+                    }),
+                    Ok(None) => {
+                        skipped.fetch_add(1, Ordering::Relaxed);
+                        progress.suspend(|| {
+                            status(&args, &format!("{}: skipped", audio.display()));
+                        });
+                    }
+                    Err(e) => {
+                        progress.suspend(|| eprintln!("{}: {e}", audio.display()));
+                        failures.lock().unwrap().push(e);
+                    }
+                }
+            });
+        }
+    });
+
+    let mut failures = failures.into_inner().unwrap();
+    let skipped = skipped.into_inner();
+    if files.len() > 1 {
+        let transcribed = files.len() - failures.len() - skipped;
+        let summary = match skipped {
+            0 => format!("Transcribed {transcribed} of {} files.", files.len()),
+            _ => format!(
+                "Transcribed {transcribed} of {} files, skipped {skipped}.",
+                files.len()
+            ),
+        };
+        status(&args, &summary);
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    let count = failures.len();
+    Err(failures
+        .swap_remove(0)
+        .context(format!("{count} of {} files failed", files.len())))
+}
+
+fn transcribe_file(
+    whisper: &Whisper,
+    audio: &Path,
+    args: &Args,
+    progress: &MultiProgress,
+) -> Result<Option<Duration>> {
+    if !args.output.should_transcribe(audio)? {
+        return Ok(None);
+    }
+
+    // shows how far along the transcription is, along with the last thing that was said
+    let bar = progress.add(ProgressBar::new(100));
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{prefix} [{elapsed_precise}] [{bar:30.cyan/blue}] {percent:>3}% ({eta}) {wide_msg}")
+            .expect("progress bar template should be valid")
+            .progress_chars("#>-"),
+    );
+    bar.set_prefix(audio.file_name().map_or_else(
+        || audio.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    ));
+
+    let callbacks = Callbacks::default()
+        .on_segment(|utterance| bar.set_message(utterance.text.trim().to_string()))
+        .on_progress(|fraction| {
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+            bar.set_position((fraction * 100.0) as u64);
+        });
+
+    let options = args.decoding.options(args.translate, args.karaoke);
+    // the samples are decoded once, for both transcribing and telling speakers apart
+    let samples = decode_file(audio)?;
+    let transcript = whisper.transcribe_samples(&samples, None, &options, callbacks);
+    bar.finish_and_clear();
+
+    let mut transcript = transcript?;
+    match args.diarize {
+        Some(DiarizeMethod::Stereo) => diarize(&mut transcript, audio, Diarization::Stereo)?,
+        Some(DiarizeMethod::Cluster) => {
+            diarize_by_voice(&mut transcript, &samples, args.speakers);
+        }
+        None => {}
+    }
+
+    args.output.write(audio, &transcript)?;
+
+    Ok(Some(transcript.processing_time))
+}
+
+/// Prints a progress message, keeping stdout clean when transcripts are being written to it.
+fn status(args: &Args, message: &str) {
+    if args.output.to_stdout() {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+/// Expands the provided files, directories and glob patterns into a sorted list of audio files.
+pub fn collect_files(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = BTreeSet::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        if path.is_file() {
+            files.insert(path.to_path_buf());
+            continue;
+        }
+
+        let pattern = if path.is_dir() {
+            // directory names can have characters like `[` that mean something in a pattern
+            Path::new(&glob::Pattern::escape(&path.to_string_lossy()))
+                .join("**")
+                .join("*")
+                .to_string_lossy()
+                .into_owned()
+        } else {
+            input.clone()
+        };
+
+        let mut matched = false;
+        for entry in glob::glob(&pattern)? {
+            let entry = entry?;
+            if entry.is_file() && (!path.is_dir() || is_audio_file(&entry)) {
+                matched = true;
+                files.insert(entry);
+            }
+        }
+
+        if !matched {
+            return Err(anyhow!("No audio files found matching {input}."));
+        }
+    }
+
+    Ok(files.into_iter().collect())
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
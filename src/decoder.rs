@@ -0,0 +1,57 @@
+use crate::{
+    error::DecodeError,
+    ffmpeg_decoder::{self, FfmpegStream},
+    resampler::deinterleave,
+};
+use std::path::Path;
+
+/// Blocks of 16kHz mono samples, decoded lazily from an audio file.
+pub type AudioStream = Box<dyn Iterator<Item = Result<Vec<f32>, DecodeError>> + Send>;
+
+/// Decodes an audio file into 16kHz mono samples.
+///
+/// When the `native-decoder` feature is enabled, the file is decoded in-process and ffmpeg is
+/// only used for containers or codecs the native decoder doesn't support.
+pub fn read_file<P: AsRef<Path>>(audio_file_path: P) -> Result<Vec<f32>, DecodeError> {
+    read_interleaved(audio_file_path, 1)
+}
+
+/// Decodes an audio file into its left and right 16kHz channels. Mono files end up with the same
+/// samples in both.
+pub fn read_file_stereo<P: AsRef<Path>>(
+    audio_file_path: P,
+) -> Result<(Vec<f32>, Vec<f32>), DecodeError> {
+    let mut channels = deinterleave(&read_interleaved(audio_file_path, 2)?, 2);
+    let right = channels.pop().unwrap_or_default();
+    let left = channels.pop().unwrap_or_default();
+
+    Ok((left, right))
+}
+
+fn read_interleaved<P: AsRef<Path>>(
+    audio_file_path: P,
+    channels: usize,
+) -> Result<Vec<f32>, DecodeError> {
+    #[cfg(feature = "native-decoder")]
+    match crate::native_decoder::read_file(&audio_file_path, channels) {
+        Ok(samples) => return Ok(samples),
+        Err(symphonia::core::errors::Error::Unsupported(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(ffmpeg_decoder::read_file(audio_file_path, channels)?)
+}
+
+/// Like [`read_file`], but yields the samples as they get decoded.
+pub fn stream_file<P: AsRef<Path>>(audio_file_path: P) -> Result<AudioStream, DecodeError> {
+    #[cfg(feature = "native-decoder")]
+    match crate::native_decoder::NativeStream::new(&audio_file_path) {
+        Ok(stream) => return Ok(Box::new(stream.map(|chunk| Ok(chunk?)))),
+        Err(symphonia::core::errors::Error::Unsupported(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(Box::new(
+        FfmpegStream::new(audio_file_path)?.map(|chunk| Ok(chunk?)),
+    ))
+}
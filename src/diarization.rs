@@ -0,0 +1,502 @@
+use crate::{
+    chunked::SAMPLES_PER_TICK,
+    decoder,
+    error::Result,
+    transcript::{Transcript, Utternace},
+};
+use std::{f32::consts::PI, path::Path};
+
+/// Length of each analysis frame (25ms at 16kHz).
+const FRAME_LENGTH: usize = 400;
+/// Distance between the start of consecutive analysis frames (10ms at 16kHz).
+const FRAME_STEP: usize = 160;
+const FFT_SIZE: usize = 512;
+const MEL_BANDS: usize = 26;
+/// Number of cepstral coefficients kept per frame, leaving out the first one (overall loudness).
+const COEFFICIENTS: usize = 12;
+/// Average cosine distance above which two clusters are considered different speakers.
+const CLUSTER_THRESHOLD: f32 = 0.8;
+/// Most utterances clustered directly. Clustering takes cubic time, so in longer recordings the
+/// rest are assigned to the closest of the speakers found among these.
+const MAX_CLUSTERED: usize = 400;
+
+/// How speakers are told apart in [`diarize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diarization {
+    /// For two-channel recordings (like most call recordings) where each speaker has their own
+    /// channel. Each utterance goes to whichever channel is louder, with the left channel being
+    /// speaker 0 and the right channel speaker 1.
+    Stereo,
+    /// Groups utterances by how similar their voices sound. When the number of speakers isn't
+    /// known, it is estimated from the audio.
+    Clustering { speakers: Option<usize> },
+}
+
+/// Assigns a speaker to each utterance of a transcript of `audio`.
+///
+/// Words (if present) are attributed to the speaker of the utterance they belong to.
+pub fn diarize<P: AsRef<Path>>(
+    transcript: &mut Transcript,
+    audio: P,
+    method: Diarization,
+) -> Result<()> {
+    let speakers = match method {
+        Diarization::Stereo => {
+            let (left, right) = decoder::read_file_stereo(audio)?;
+            by_channel(&transcript.utterances, &left, &right)
+        }
+        Diarization::Clustering { speakers } => {
+            let samples = decoder::read_file(audio)?;
+            by_voice(&transcript.utterances, &samples, speakers)
+        }
+    };
+
+    assign(transcript, speakers);
+    Ok(())
+}
+
+/// Like [`diarize`] with [`Diarization::Clustering`], for audio that has already been decoded (as
+/// 16kHz mono samples), so it doesn't have to be decoded again.
+pub fn diarize_by_voice(transcript: &mut Transcript, samples: &[f32], speakers: Option<usize>) {
+    let speakers = by_voice(&transcript.utterances, samples, speakers);
+    assign(transcript, speakers);
+}
+
+fn assign(transcript: &mut Transcript, speakers: Vec<Option<usize>>) {
+    for (utterance, speaker) in transcript.utterances.iter_mut().zip(speakers) {
+        utterance.speaker = speaker;
+    }
+
+    if let Some(words) = &mut transcript.word_utterances {
+        for word in words {
+            word.speaker = word
+                .utterance
+                .and_then(|utterance| transcript.utterances.get(utterance))
+                .and_then(|utterance| utterance.speaker);
+        }
+    }
+}
+
+/// Picks the louder channel for each utterance.
+fn by_channel(utterances: &[Utternace], left: &[f32], right: &[f32]) -> Vec<Option<usize>> {
+    utterances
+        .iter()
+        .map(|utterance| {
+            let left = energy(span(left, utterance));
+            let right = energy(span(right, utterance));
+
+            if left == 0.0 && right == 0.0 {
+                None
+            } else if left >= right {
+                Some(0)
+            } else {
+                Some(1)
+            }
+        })
+        .collect()
+}
+
+/// Clusters a voice fingerprint of each utterance, numbering speakers in order of appearance.
+fn by_voice(
+    utterances: &[Utternace],
+    samples: &[f32],
+    speakers: Option<usize>,
+) -> Vec<Option<usize>> {
+    let embeddings = utterances
+        .iter()
+        .map(|utterance| embedding(span(samples, utterance)))
+        .collect::<Vec<_>>();
+
+    let voiced = embeddings.iter().flatten().cloned().collect::<Vec<_>>();
+    let mut clusters = cluster_all(&normalize(voiced), speakers).into_iter();
+
+    let mut order = Vec::new();
+    embeddings
+        .iter()
+        .map(|embedding| {
+            embedding.as_ref()?;
+            let cluster = clusters.next()?;
+
+            Some(order.iter().position(|&c| c == cluster).unwrap_or_else(|| {
+                order.push(cluster);
+                order.len() - 1
+            }))
+        })
+        .collect()
+}
+
+/// The samples covered by an utterance.
+fn span<'a>(samples: &'a [f32], utterance: &Utternace) -> &'a [f32] {
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    let [start, stop] = [utterance.start, utterance.stop]
+        .map(|tick| (tick.max(0) as usize * SAMPLES_PER_TICK).min(samples.len()));
+
+    &samples[start..stop.max(start)]
+}
+
+fn energy(samples: &[f32]) -> f32 {
+    samples.iter().map(|sample| sample * sample).sum()
+}
+
+/// Summarizes the voice in a stretch of audio as the mean and standard deviation of its
+/// mel-frequency cepstral coefficients. Returns `None` if there is no audible frame in it.
+fn embedding(samples: &[f32]) -> Option<Vec<f32>> {
+    let frames = samples
+        .windows(FRAME_LENGTH)
+        .step_by(FRAME_STEP)
+        .collect::<Vec<_>>();
+
+    let loudest = frames.iter().map(|frame| energy(frame)).fold(0.0, f32::max);
+    if loudest == 0.0 {
+        return None;
+    }
+
+    // leave out near-silent frames, which say more about the background than about the speaker
+    let filters = mel_filters();
+    let coefficients = frames
+        .iter()
+        .filter(|frame| energy(frame) >= loudest / 100.0)
+        .map(|frame| cepstrum(frame, &filters))
+        .collect::<Vec<_>>();
+
+    #[allow(clippy::cast_precision_loss)]
+    let count = coefficients.len() as f32;
+    let mean = (0..COEFFICIENTS)
+        .map(|i| coefficients.iter().map(|c| c[i]).sum::<f32>() / count)
+        .collect::<Vec<_>>();
+    let deviation = (0..COEFFICIENTS).map(|i| {
+        (coefficients
+            .iter()
+            .map(|c| (c[i] - mean[i]).powi(2))
+            .sum::<f32>()
+            / count)
+            .sqrt()
+    });
+
+    Some(mean.iter().copied().chain(deviation).collect())
+}
+
+/// Computes the cepstral coefficients of a single frame.
+fn cepstrum(frame: &[f32], filters: &[Vec<f32>]) -> Vec<f32> {
+    #[allow(clippy::cast_precision_loss)]
+    let mut re = frame
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| {
+            sample * 0.5f32.mul_add(-(2.0 * PI * i as f32 / FRAME_LENGTH as f32).cos(), 0.5)
+        })
+        .chain(std::iter::repeat(0.0))
+        .take(FFT_SIZE)
+        .collect::<Vec<_>>();
+    let mut im = vec![0.0; FFT_SIZE];
+    fft(&mut re, &mut im);
+
+    let power = (0..=FFT_SIZE / 2)
+        .map(|bin| re[bin].mul_add(re[bin], im[bin] * im[bin]))
+        .collect::<Vec<_>>();
+
+    let bands = filters
+        .iter()
+        .map(|filter| {
+            let band = filter.iter().zip(&power).map(|(w, p)| w * p).sum::<f32>();
+            band.max(1e-10).ln()
+        })
+        .collect::<Vec<_>>();
+
+    // DCT-II of the log mel energies
+    #[allow(clippy::cast_precision_loss)]
+    (1..=COEFFICIENTS)
+        .map(|k| {
+            bands
+                .iter()
+                .enumerate()
+                .map(|(n, band)| band * (PI * k as f32 * (n as f32 + 0.5) / MEL_BANDS as f32).cos())
+                .sum()
+        })
+        .collect()
+}
+
+/// Triangular filters spaced evenly on the mel scale, up to the 8kHz Nyquist frequency.
+fn mel_filters() -> Vec<Vec<f32>> {
+    let mel = |hz: f32| 2595.0 * (1.0 + hz / 700.0).log10();
+    let hz = |mel: f32| 700.0 * (10f32.powf(mel / 2595.0) - 1.0);
+
+    #[allow(clippy::cast_precision_loss)]
+    let edges = (0..MEL_BANDS + 2)
+        .map(|i| hz(mel(8000.0) * i as f32 / (MEL_BANDS + 1) as f32) * FFT_SIZE as f32 / 16000.0)
+        .collect::<Vec<_>>();
+
+    edges
+        .windows(3)
+        .map(|edge| {
+            #[allow(clippy::cast_precision_loss)]
+            (0..=FFT_SIZE / 2)
+                .map(|bin| {
+                    let bin = bin as f32;
+                    if bin <= edge[0] || bin >= edge[2] {
+                        0.0
+                    } else if bin <= edge[1] {
+                        (bin - edge[0]) / (edge[1] - edge[0])
+                    } else {
+                        (edge[2] - bin) / (edge[2] - edge[1])
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// In-place radix-2 fast Fourier transform. The length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        #[allow(clippy::cast_precision_loss)]
+        let angle = -2.0 * PI / len as f32;
+
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                #[allow(clippy::cast_precision_loss)]
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+
+                let t_re = re[b].mul_add(cos, -im[b] * sin);
+                let t_im = re[b].mul_add(sin, im[b] * cos);
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+
+        len <<= 1;
+    }
+}
+
+/// Scales every dimension to zero mean and unit variance, so no single coefficient dominates the
+/// distances between embeddings.
+fn normalize(mut embeddings: Vec<Vec<f32>>) -> Vec<Vec<f32>> {
+    let Some(dimensions) = embeddings.first().map(Vec::len) else {
+        return embeddings;
+    };
+
+    #[allow(clippy::cast_precision_loss)]
+    let count = embeddings.len() as f32;
+    for i in 0..dimensions {
+        let mean = embeddings.iter().map(|e| e[i]).sum::<f32>() / count;
+        let deviation = (embeddings
+            .iter()
+            .map(|e| (e[i] - mean).powi(2))
+            .sum::<f32>()
+            / count)
+            .sqrt()
+            .max(1e-6);
+
+        for embedding in &mut embeddings {
+            embedding[i] = (embedding[i] - mean) / deviation;
+        }
+    }
+
+    embeddings
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+    let norm = energy(a).sqrt() * energy(b).sqrt();
+
+    if norm == 0.0 {
+        1.0
+    } else {
+        1.0 - dot / norm
+    }
+}
+
+/// Clusters an evenly spread selection of at most [`MAX_CLUSTERED`] embeddings, and assigns the
+/// rest to the cluster with the closest centroid. Returns the cluster of each embedding.
+fn cluster_all(embeddings: &[Vec<f32>], speakers: Option<usize>) -> Vec<usize> {
+    if embeddings.len() <= MAX_CLUSTERED {
+        return cluster(embeddings, speakers);
+    }
+
+    let picked = (0..MAX_CLUSTERED)
+        .map(|i| i * embeddings.len() / MAX_CLUSTERED)
+        .collect::<Vec<_>>();
+    let sample = picked
+        .iter()
+        .map(|&i| embeddings[i].clone())
+        .collect::<Vec<_>>();
+    let clusters = cluster(&sample, speakers);
+
+    // sums of the embeddings in each cluster, which point the same way as their means (all that
+    // cosine distance looks at)
+    let mut centroids: Vec<(usize, Vec<f32>)> = Vec::new();
+    for (embedding, &cluster) in sample.iter().zip(&clusters) {
+        match centroids.iter_mut().find(|(c, _)| *c == cluster) {
+            Some((_, sum)) => {
+                for (total, value) in sum.iter_mut().zip(embedding) {
+                    *total += value;
+                }
+            }
+            None => centroids.push((cluster, embedding.clone())),
+        }
+    }
+
+    embeddings
+        .iter()
+        .map(|embedding| {
+            centroids
+                .iter()
+                .min_by(|(_, a), (_, b)| {
+                    cosine_distance(embedding, a).total_cmp(&cosine_distance(embedding, b))
+                })
+                .map_or(0, |(cluster, _)| *cluster)
+        })
+        .collect()
+}
+
+/// Average-linkage agglomerative clustering. Merges the closest clusters until there are
+/// `speakers` of them left or, if that isn't known, until they are all further apart than
+/// [`CLUSTER_THRESHOLD`]. Returns the cluster of each embedding.
+fn cluster(embeddings: &[Vec<f32>], speakers: Option<usize>) -> Vec<usize> {
+    let n = embeddings.len();
+    let mut distances = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| cosine_distance(&embeddings[i], &embeddings[j]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut sizes = vec![1usize; n];
+    let mut active = (0..n).collect::<Vec<_>>();
+    let mut assignment = (0..n).collect::<Vec<_>>();
+
+    while active.len() > speakers.unwrap_or(1).max(1) {
+        let mut closest = (f32::INFINITY, 0, 0);
+        for (x, &a) in active.iter().enumerate() {
+            for &b in &active[x + 1..] {
+                if distances[a][b] < closest.0 {
+                    closest = (distances[a][b], a, b);
+                }
+            }
+        }
+
+        let (distance, a, b) = closest;
+        if speakers.is_none() && distance > CLUSTER_THRESHOLD {
+            break;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        for &c in &active {
+            let merged = distances[a][c]
+                .mul_add(sizes[a] as f32, distances[b][c] * sizes[b] as f32)
+                / (sizes[a] + sizes[b]) as f32;
+            distances[a][c] = merged;
+            distances[c][a] = merged;
+        }
+
+        sizes[a] += sizes[b];
+        active.retain(|&c| c != b);
+        for cluster in &mut assignment {
+            if *cluster == b {
+                *cluster = a;
+            }
+        }
+    }
+
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fft_peaks_at_the_frequency_of_a_sine() {
+        #[allow(clippy::cast_precision_loss)]
+        let mut re = (0..FFT_SIZE)
+            .map(|i| (2.0 * PI * 32.0 * i as f32 / FFT_SIZE as f32).sin())
+            .collect::<Vec<_>>();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+
+        let magnitudes = re
+            .iter()
+            .zip(&im)
+            .take(FFT_SIZE / 2)
+            .map(|(re, im)| re.hypot(*im))
+            .collect::<Vec<_>>();
+        let peak = (0..magnitudes.len())
+            .max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))
+            .unwrap();
+
+        assert_eq!(peak, 32);
+        #[allow(clippy::cast_precision_loss)]
+        let expected = FFT_SIZE as f32 / 2.0;
+        assert!((magnitudes[32] - expected).abs() < 1e-2 * expected);
+        assert!(magnitudes
+            .iter()
+            .enumerate()
+            .all(|(i, m)| i == 32 || *m < 1e-2 * expected));
+    }
+
+    fn voices(count: usize) -> Vec<Vec<f32>> {
+        #[allow(clippy::cast_precision_loss)]
+        (0..count)
+            .map(|i| {
+                let jitter = (i % 7) as f32 * 0.01;
+                if i % 2 == 0 {
+                    vec![1.0, jitter, 0.0]
+                } else {
+                    vec![0.0, jitter, 1.0]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clusters_distinct_voices_apart() {
+        let clusters = cluster(&voices(10), None);
+
+        for (i, cluster) in clusters.iter().enumerate() {
+            assert_eq!(*cluster == clusters[0], i % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn clusters_into_the_given_number_of_speakers() {
+        let clusters = cluster(&voices(10), Some(1));
+        assert!(clusters.iter().all(|cluster| *cluster == clusters[0]));
+
+        let clusters = cluster(&voices(10), Some(4));
+        let mut distinct = clusters.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 4);
+    }
+
+    #[test]
+    fn assigns_embeddings_past_the_clustered_ones_to_the_closest_cluster() {
+        let clusters = cluster_all(&voices(MAX_CLUSTERED * 3), None);
+
+        for (i, cluster) in clusters.iter().enumerate() {
+            assert_eq!(*cluster == clusters[0], i % 2 == 0);
+        }
+    }
+}
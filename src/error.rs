@@ -0,0 +1,58 @@
+use crate::ffmpeg_decoder::FfmpegError;
+use std::{io, path::PathBuf};
+use whisper_rs::WhisperError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to download {url}: {source}")]
+    Download {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("downloaded file {} is corrupted (expected SHA-256 {expected}, got {actual})", path.display())]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    #[error("can't verify {url}: the server didn't report its checksum, and none is built in")]
+    UnknownChecksum { url: String },
+    #[error("failed to decode audio: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("model not found at {}", path.display())]
+    ModelNotFound { path: PathBuf },
+    #[error("failed to load model from {}: {source}", path.display())]
+    ModelLoad {
+        path: PathBuf,
+        #[source]
+        source: WhisperError,
+    },
+    #[error("{0} has no known alignment heads, so its words can't be aligned with DTW")]
+    NoAlignmentHeads(String),
+    #[error("failed to transcribe audio: {0}")]
+    Inference(#[from] WhisperError),
+    #[error("token {0} is not in the model's vocabulary")]
+    InvalidToken(i32),
+    #[error("no speech found in audio")]
+    NoSpeech,
+    #[error("transcription was cancelled")]
+    Cancelled,
+    #[error("invalid options: {0}")]
+    InvalidOptions(String),
+    #[error("invalid transcript: {0}")]
+    InvalidTranscript(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error(transparent)]
+    Ffmpeg(#[from] FfmpegError),
+    #[cfg(feature = "native-decoder")]
+    #[error(transparent)]
+    Native(#[from] symphonia::core::errors::Error),
+}
@@ -1,47 +1,163 @@
-use anyhow::{anyhow, Result};
-use audrey::Reader;
-use std::env::temp_dir;
-use std::path::Path;
-use std::process::Stdio;
-use std::{fs::File, process::Command};
-
-// ffmpeg -i input.mp3 -ar 16000 output.wav
-fn use_ffmpeg<P: AsRef<Path>>(input_path: P) -> Result<Vec<i16>> {
-    let temp_file = temp_dir().join(format!("{}.wav", uuid::Uuid::new_v4()));
-    let mut pid = Command::new("ffmpeg")
-        .args([
-            "-i",
-            input_path
-                .as_ref()
-                .to_str()
-                .ok_or_else(|| anyhow!("invalid path"))?,
-            "-ar",
-            "16000",
-            "-ac",
-            "1",
-            "-c:a",
-            "pcm_s16le",
-            (temp_file.to_str().unwrap()),
-            "-hide_banner",
-            "-y",
-            "-loglevel",
-            "error",
-        ])
-        .stdin(Stdio::null())
-        .spawn()?;
-
-    if pid.wait()?.success() {
-        let output = File::open(&temp_file)?;
-        let mut reader = Reader::new(output)?;
-        let samples: Result<Vec<i16>, _> = reader.samples().collect();
-        std::fs::remove_file(temp_file)?;
-        samples.map_err(std::convert::Into::into)
-    } else {
-        Err(anyhow!("unable to convert file"))
+use std::{
+    io::{self, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FfmpegError {
+    #[error("failed to run ffmpeg: {0}")]
+    Spawn(#[source] io::Error),
+    #[error("failed to read ffmpeg output: {0}")]
+    Io(#[from] io::Error),
+    #[error("ffmpeg exited with {status}: {message}")]
+    Failed { status: ExitStatus, message: String },
+}
+
+/// Streams 16kHz samples out of ffmpeg, without writing anything to disk.
+pub struct FfmpegStream {
+    child: Child,
+    stdout: ChildStdout,
+    stderr: Option<JoinHandle<String>>,
+    leftover: Option<u8>,
+    finished: bool,
+}
+
+impl FfmpegStream {
+    /// Decodes the file as mono.
+    pub fn new<P: AsRef<Path>>(input_path: P) -> Result<Self, FfmpegError> {
+        Self::with_channels(input_path, 1)
+    }
+
+    /// Decodes the file into `channels` interleaved channels, letting ffmpeg up- or downmix it.
+    pub fn with_channels<P: AsRef<Path>>(
+        input_path: P,
+        channels: usize,
+    ) -> Result<Self, FfmpegError> {
+        // ffmpeg -i input.mp3 -f s16le -ar 16000 -ac 1 -
+        let mut child = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-i"])
+            .arg(input_path.as_ref())
+            .args(["-f", "s16le", "-ar", "16000", "-ac"])
+            .arg(channels.to_string())
+            .args(["-c:a", "pcm_s16le", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(FfmpegError::Spawn)?;
+
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let mut stderr = child.stderr.take().expect("stderr should be piped");
+
+        Ok(Self {
+            child,
+            stdout,
+            leftover: None,
+            finished: false,
+            stderr: Some(thread::spawn(move || {
+                let mut message = String::new();
+                let _ = stderr.read_to_string(&mut message);
+                message
+            })),
+        })
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Vec<f32>>, FfmpegError> {
+        let mut bytes = vec![0; CHUNK_SIZE];
+        let mut offset = 0;
+        if let Some(byte) = self.leftover.take() {
+            bytes[0] = byte;
+            offset = 1;
+        }
+
+        let read = loop {
+            match self.stdout.read(&mut bytes[offset..]) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        if read == 0 {
+            self.finish()?;
+            return Ok(None);
+        }
+
+        bytes.truncate(offset + read);
+        if !bytes.len().is_multiple_of(2) {
+            self.leftover = bytes.pop();
+        }
+
+        Ok(Some(
+            bytes
+                .chunks_exact(2)
+                .map(|pair| f32::from(i16::from_le_bytes([pair[0], pair[1]])) / 32768.0)
+                .collect(),
+        ))
+    }
+
+    fn finish(&mut self) -> Result<(), FfmpegError> {
+        self.finished = true;
+        let status = self.child.wait()?;
+        let message = self
+            .stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(FfmpegError::Failed {
+                status,
+                message: message.trim().to_string(),
+            })
+        }
+    }
+}
+
+impl Iterator for FfmpegStream {
+    type Item = Result<Vec<f32>, FfmpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.read_chunk() {
+            Ok(Some(samples)) => Some(Ok(samples)),
+            Ok(None) => None,
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
     }
 }
 
-pub fn read_file<P: AsRef<Path>>(audio_file_path: P) -> Result<Vec<f32>> {
-    let audio_buf = use_ffmpeg(&audio_file_path)?;
-    Ok(whisper_rs::convert_integer_to_float_audio(&audio_buf))
+impl Drop for FfmpegStream {
+    fn drop(&mut self) {
+        // the stream can stop early (when reading fails, or isn't read to the end), so check with
+        // the child itself rather than `finished` whether it still has to be reaped
+        if !matches!(self.child.try_wait(), Ok(Some(_))) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+pub fn read_file<P: AsRef<Path>>(
+    audio_file_path: P,
+    channels: usize,
+) -> Result<Vec<f32>, FfmpegError> {
+    let mut samples = Vec::new();
+    for chunk in FfmpegStream::with_channels(audio_file_path, channels)? {
+        samples.extend(chunk?);
+    }
+
+    Ok(samples)
 }
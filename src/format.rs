@@ -0,0 +1,205 @@
+mod delimited;
+mod lrc;
+mod timestamped;
+mod ttml;
+
+use crate::{ass::AssOptions, transcript::Transcript};
+use clap::{builder::PossibleValue, ValueEnum};
+use std::fmt;
+
+pub use delimited::{Csv, Tsv};
+pub use lrc::Lrc;
+pub use timestamped::TimestampedText;
+pub use ttml::Ttml;
+
+/// Renders a [`Transcript`] as a file. Implement it to add a format of your own.
+pub trait Formatter {
+    /// Extension of the files the format is saved as, without the leading dot.
+    fn extension(&self) -> &'static str;
+
+    fn render(&self, transcript: &Transcript) -> String;
+
+    /// Whether the format is for subtitles, which get split into cues that fit on screen before
+    /// they're rendered.
+    fn is_subtitle(&self) -> bool {
+        false
+    }
+}
+
+/// One utterance per line.
+pub struct PlainText;
+
+impl Formatter for PlainText {
+    fn extension(&self) -> &'static str {
+        "txt"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        transcript.as_text()
+    }
+}
+
+pub struct Vtt;
+
+impl Formatter for Vtt {
+    fn extension(&self) -> &'static str {
+        "vtt"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        transcript.as_vtt()
+    }
+
+    fn is_subtitle(&self) -> bool {
+        true
+    }
+}
+
+pub struct Srt;
+
+impl Formatter for Srt {
+    fn extension(&self) -> &'static str {
+        "srt"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        transcript.as_srt()
+    }
+
+    fn is_subtitle(&self) -> bool {
+        true
+    }
+}
+
+/// The whole transcript, including its tokens and the options it was generated with.
+pub struct Json;
+
+impl Formatter for Json {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        transcript.as_json()
+    }
+}
+
+/// One JSON object per utterance, one per line.
+pub struct JsonLines;
+
+impl Formatter for JsonLines {
+    fn extension(&self) -> &'static str {
+        "jsonl"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        transcript.as_json_lines()
+    }
+}
+
+/// Advanced SubStation Alpha subtitles, in the given style.
+#[derive(Default)]
+pub struct Ass(pub AssOptions);
+
+impl Formatter for Ass {
+    fn extension(&self) -> &'static str {
+        "ass"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        transcript.as_ass_with(&self.0)
+    }
+
+    fn is_subtitle(&self) -> bool {
+        true
+    }
+}
+
+/// A format a [`Transcript`] can be rendered as from the command line, picked by its name.
+#[derive(Clone, Copy)]
+pub struct OutputFormat {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    formatter: fn() -> Box<dyn Formatter>,
+}
+
+impl OutputFormat {
+    /// Every format that can be picked from the command line, in the order they're listed in
+    /// `--help`. Adding one is all it takes to make a [`Formatter`] available there.
+    pub const ALL: &'static [Self] = &[
+        Self::new("txt", || Box::new(PlainText)),
+        Self::new("vtt", || Box::new(Vtt)),
+        Self::new("srt", || Box::new(Srt)),
+        Self::new("json", || Box::new(Json)),
+        Self::new("jsonl", || Box::new(JsonLines)),
+        Self::new("ass", || Box::new(Ass::default())),
+        Self::new("tsv", || Box::new(Tsv)),
+        Self::new("csv", || Box::new(Csv)),
+        Self::new("lrc", || Box::new(Lrc)),
+        Self::new("ttml", || Box::new(Ttml)).with_aliases(&["dfxp"]),
+        Self::new("timestamped", || Box::new(TimestampedText)),
+    ];
+
+    pub const fn new(name: &'static str, formatter: fn() -> Box<dyn Formatter>) -> Self {
+        Self {
+            name,
+            formatter,
+            aliases: &[],
+        }
+    }
+
+    /// Other names the format can be picked by.
+    pub const fn with_aliases(self, aliases: &'static [&'static str]) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Looks up a format by its name or one of its aliases.
+    pub fn from_name(name: &str) -> Option<Self> {
+        <Self as ValueEnum>::from_str(name, true).ok()
+    }
+
+    pub const fn name(self) -> &'static str {
+        self.name
+    }
+
+    pub fn formatter(self) -> Box<dyn Formatter> {
+        (self.formatter)()
+    }
+
+    pub fn extension(self) -> &'static str {
+        self.formatter().extension()
+    }
+
+    pub fn is_subtitle(self) -> bool {
+        self.formatter().is_subtitle()
+    }
+
+    pub fn render(self, transcript: &Transcript) -> String {
+        self.formatter().render(transcript)
+    }
+}
+
+/// Formats are told apart by their name.
+impl PartialEq for OutputFormat {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for OutputFormat {}
+
+impl fmt::Debug for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        Self::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.name).aliases(self.aliases.iter().copied()))
+    }
+}
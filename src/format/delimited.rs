@@ -0,0 +1,52 @@
+use super::Formatter;
+use crate::transcript::Transcript;
+
+/// Tab-separated values, with the start and end of each utterance in milliseconds. Tabs and line
+/// breaks in the text are replaced with spaces.
+pub struct Tsv;
+
+impl Formatter for Tsv {
+    fn extension(&self) -> &'static str {
+        "tsv"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        render(transcript, '\t', |text| text.replace(['\t', '\n'], " "))
+    }
+}
+
+/// Comma-separated values, with the start and end of each utterance in milliseconds. The text is
+/// quoted when it has to be.
+pub struct Csv;
+
+impl Formatter for Csv {
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        render(transcript, ',', |text| {
+            if text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text.to_string()
+            }
+        })
+    }
+}
+
+fn render(transcript: &Transcript, separator: char, escape: impl Fn(&str) -> String) -> String {
+    transcript.utterances.iter().fold(
+        format!("start{separator}end{separator}text\n"),
+        |table, fragment| {
+            table
+                + format!(
+                    "{}{separator}{}{separator}{}\n",
+                    fragment.start * 10,
+                    fragment.stop * 10,
+                    escape(&fragment.labelled_text())
+                )
+                .as_str()
+        },
+    )
+}
@@ -0,0 +1,47 @@
+use super::Formatter;
+use crate::{transcript::Transcript, utils::split_timestamp};
+
+/// Lyrics for music players, with one line per utterance. With word timestamps, each word is
+/// timed too (as in enhanced LRC), so players that support it can highlight them.
+pub struct Lrc;
+
+impl Formatter for Lrc {
+    fn extension(&self) -> &'static str {
+        "lrc"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        let mut lrc = String::new();
+        if let Some(language) = transcript.language {
+            lrc += &format!("[la:{}]\n", <&str>::from(language));
+        }
+
+        let words = transcript.words_by_utterance();
+        for (i, fragment) in transcript.utterances.iter().enumerate() {
+            let words = words.as_ref().map(|words| &words[i]);
+            let text = match words.filter(|words| !words.is_empty()) {
+                Some(words) => words
+                    .iter()
+                    .map(|word| format!("<{}>{}", timestamp(word.start), word.text.trim()))
+                    .collect::<Vec<_>>()
+                    .join(transcript.word_separator()),
+                None => fragment.labelled_text(),
+            };
+
+            lrc += &format!(
+                "[{}]{}\n",
+                timestamp(fragment.start),
+                text.replace('\n', " ")
+            );
+        }
+
+        lrc
+    }
+}
+
+/// Formats a timestamp (in centiseconds) like `01:02.50`. Minutes go past 59 rather than adding
+/// hours, which LRC doesn't have.
+fn timestamp(ticks: i64) -> String {
+    let (hours, minutes, seconds, centiseconds) = split_timestamp(ticks);
+    format!("{:02}:{seconds:02}.{centiseconds:02}", hours * 60 + minutes)
+}
@@ -0,0 +1,27 @@
+use super::Formatter;
+use crate::{transcript::Transcript, utils::split_timestamp};
+
+/// Plain text with the time each utterance starts at, like `[00:01:02] Hello`.
+pub struct TimestampedText;
+
+impl Formatter for TimestampedText {
+    /// Saved next to the plain text transcript, rather than replacing it.
+    fn extension(&self) -> &'static str {
+        "timestamped.txt"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        transcript
+            .utterances
+            .iter()
+            .fold(String::new(), |text, fragment| {
+                let (hours, minutes, seconds, _) = split_timestamp(fragment.start);
+
+                text + format!(
+                    "[{hours:02}:{minutes:02}:{seconds:02}] {}\n",
+                    fragment.labelled_text()
+                )
+                .as_str()
+            })
+    }
+}
@@ -0,0 +1,83 @@
+use super::Formatter;
+use crate::{transcript::Transcript, utils::split_timestamp};
+
+/// Timed Text Markup Language subtitles (also known as DFXP), with speakers as `ttm:agent`s
+/// when the transcript has been diarized.
+pub struct Ttml;
+
+impl Formatter for Ttml {
+    fn extension(&self) -> &'static str {
+        "ttml"
+    }
+
+    fn render(&self, transcript: &Transcript) -> String {
+        let language = transcript.language.map_or("", <&str>::from);
+        let mut speakers = transcript
+            .utterances
+            .iter()
+            .filter_map(|utterance| utterance.speaker)
+            .collect::<Vec<_>>();
+        speakers.sort_unstable();
+        speakers.dedup();
+
+        let mut ttml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tt xmlns=\"http://www.w3.org/ns/ttml\" \
+             xmlns:ttm=\"http://www.w3.org/ns/ttml#metadata\" xml:lang=\"{language}\">\n"
+        );
+
+        if !speakers.is_empty() {
+            ttml += "  <head>\n    <metadata>\n";
+            for speaker in speakers {
+                ttml += &format!(
+                    "      <ttm:agent xml:id=\"speaker{0}\" type=\"person\">\n        <ttm:name \
+                     type=\"alias\">Speaker {0}</ttm:name>\n      </ttm:agent>\n",
+                    speaker + 1
+                );
+            }
+            ttml += "    </metadata>\n  </head>\n";
+        }
+
+        ttml += "  <body>\n    <div>\n";
+        for fragment in &transcript.utterances {
+            let agent = fragment
+                .speaker
+                .map(|speaker| format!(" ttm:agent=\"speaker{}\"", speaker + 1))
+                .unwrap_or_default();
+            let text = fragment
+                .text
+                .trim()
+                .lines()
+                .map(escape)
+                .collect::<Vec<_>>()
+                .join("<br/>");
+
+            ttml += &format!(
+                "      <p begin=\"{}\" end=\"{}\"{agent}>{text}</p>\n",
+                timestamp(fragment.start),
+                timestamp(fragment.stop),
+            );
+        }
+        ttml += "    </div>\n  </body>\n</tt>\n";
+
+        ttml
+    }
+
+    fn is_subtitle(&self) -> bool {
+        true
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Formats a timestamp (in centiseconds) as a TTML clock time, like `00:01:02.500`.
+fn timestamp(ticks: i64) -> String {
+    let (hours, minutes, seconds, centiseconds) = split_timestamp(ticks);
+    format!(
+        "{hours:02}:{minutes:02}:{seconds:02}.{:03}",
+        centiseconds * 10
+    )
+}
@@ -0,0 +1,461 @@
+use crate::{
+    transcript::{self, Transcript, Utternace},
+    words,
+};
+use std::time::Duration;
+
+/// Rules for splitting a transcript into subtitle cues, for [`Transcript::with_layout`].
+///
+/// The defaults follow common broadcast guidelines: two lines of at most 42 characters, on
+/// screen for at most 7 seconds, read at no more than 17 characters per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubtitleLayout {
+    /// Longest a line can be, in characters. Words that are longer than this get a line of their
+    /// own.
+    pub max_line_length: usize,
+    /// Most lines a cue can have.
+    pub max_lines: usize,
+    /// Longest a cue can stay on screen.
+    pub max_duration: Duration,
+    /// Shortest gap between two cues, so viewers notice one replacing the other.
+    pub min_gap: Duration,
+    /// Fastest the text should have to be read, in characters per second. Cues are kept on screen
+    /// longer to make up for it, as long as there's room before the next one.
+    pub max_cps: f32,
+}
+
+impl Default for SubtitleLayout {
+    fn default() -> Self {
+        Self {
+            max_lines: 2,
+            max_cps: 17.0,
+            max_line_length: 42,
+            min_gap: Duration::from_millis(80),
+            max_duration: Duration::from_secs(7),
+        }
+    }
+}
+
+/// A word and when it's spoken, in centiseconds.
+struct Word<'a> {
+    text: &'a str,
+    start: i64,
+    stop: i64,
+    /// The word in the transcript's word timestamps, unless it was estimated.
+    source: Option<&'a Utternace>,
+}
+
+/// Splits the utterances of `transcript` into cues that follow `layout`, with their text broken
+/// into lines, along with the words of the transcript (if it has word timestamps) pointing to the
+/// cue they ended up in.
+///
+/// Utterances are split between words, at the end of a sentence or clause when there's one close
+/// enough. Without word timestamps, the time each word is spoken is estimated from its length.
+pub(crate) fn lay_out(
+    transcript: &Transcript,
+    layout: &SubtitleLayout,
+) -> (Vec<Utternace>, Option<Vec<Utternace>>) {
+    let unspaced = words::is_unspaced(transcript.language);
+    let words_by_utterance = transcript.words_by_utterance();
+
+    let mut cues = Vec::new();
+    let mut cue_words = Vec::new();
+    for (i, utterance) in transcript.utterances.iter().enumerate() {
+        let confidence = utterance
+            .confidence
+            .or_else(|| transcript::average_p(&utterance.tokens));
+        let words = match words_by_utterance.as_ref().map(|words| &words[i]) {
+            Some(words) if !words.is_empty() => words
+                .iter()
+                .map(|word| Word {
+                    text: word.text.trim(),
+                    start: word.start,
+                    stop: word.stop,
+                    source: Some(word),
+                })
+                .collect(),
+            _ => estimate_words(utterance, unspaced),
+        };
+
+        for group in split(&words, layout, unspaced) {
+            cue_words.extend(group.iter().filter_map(|word| {
+                Some(Utternace {
+                    utterance: Some(cues.len()),
+                    ..word.source?.clone()
+                })
+            }));
+
+            cues.push(Utternace {
+                start: group[0].start,
+                stop: group[group.len() - 1].stop,
+                text: wrap(group, layout, unspaced),
+                tokens: Vec::new(),
+                speaker: utterance.speaker,
+                confidence,
+                utterance: None,
+            });
+        }
+    }
+
+    fit_timing(&mut cues, layout);
+    (cues, words_by_utterance.map(|_| cue_words))
+}
+
+/// Splits the text of `utterance` into words, spreading its duration between them by length.
+fn estimate_words(utterance: &Utternace, unspaced: bool) -> Vec<Word<'_>> {
+    let text = utterance.text.trim();
+    let pieces = if unspaced {
+        text.char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .map(|(i, c)| &text[i..i + c.len_utf8()])
+            .collect::<Vec<_>>()
+    } else {
+        text.split_whitespace().collect()
+    };
+
+    let total = pieces
+        .iter()
+        .map(|piece| length(piece))
+        .sum::<usize>()
+        .max(1);
+    let span = utterance.stop - utterance.start;
+    let at = |chars: usize| {
+        let share = i64::try_from(chars).unwrap_or_default();
+        utterance.start + span * share / i64::try_from(total).unwrap_or(i64::MAX)
+    };
+
+    let mut elapsed = 0;
+    pieces
+        .into_iter()
+        .map(|text| {
+            let start = at(elapsed);
+            elapsed += length(text);
+
+            Word {
+                text,
+                start,
+                stop: at(elapsed),
+                source: None,
+            }
+        })
+        .collect()
+}
+
+/// Groups `words` into cues that fit on screen and aren't shown for too long.
+fn split<'w, 'a>(
+    words: &'w [Word<'a>],
+    layout: &SubtitleLayout,
+    unspaced: bool,
+) -> Vec<&'w [Word<'a>]> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for end in 1..=words.len() {
+        while end - start > 1 && !fits(&words[start..end], layout, unspaced) {
+            let cut = start + best_break(&words[start..end - 1], unspaced);
+            groups.push(&words[start..cut]);
+            start = cut;
+        }
+
+        // a new sentence gets a new cue, unless this one is still short
+        let cue = &words[start..end];
+        if ends_sentence(cue[cue.len() - 1].text)
+            && text_length(cue, unspaced) >= layout.max_line_length
+        {
+            groups.push(cue);
+            start = end;
+        }
+    }
+
+    if start < words.len() {
+        groups.push(&words[start..]);
+    }
+
+    groups
+}
+
+fn fits(words: &[Word], layout: &SubtitleLayout, unspaced: bool) -> bool {
+    lines_needed(words, layout.max_line_length, unspaced) <= layout.max_lines
+        && words[words.len() - 1].stop - words[0].start <= ticks(layout.max_duration)
+}
+
+/// How many of `words` go in the first cue, when they don't all fit in one. Prefers breaking
+/// after the end of a sentence, then after a clause, as long as the cue is at least half full.
+fn best_break(words: &[Word], unspaced: bool) -> usize {
+    let full = text_length(words, unspaced);
+    let candidates = || {
+        (1..words.len())
+            .rev()
+            .filter(|&i| 2 * text_length(&words[..i], unspaced) >= full)
+    };
+
+    candidates()
+        .find(|&i| ends_sentence(words[i - 1].text))
+        .or_else(|| candidates().find(|&i| ends_clause(words[i - 1].text)))
+        .unwrap_or(words.len())
+}
+
+/// Joins `words` into as few lines as they fit in, keeping the lines about the same length.
+fn wrap(words: &[Word], layout: &SubtitleLayout, unspaced: bool) -> String {
+    let lines = lines_needed(words, layout.max_line_length, unspaced).min(words.len());
+
+    // longest[k][i] is the longest line when the first i words are split into k lines
+    let mut longest = vec![vec![usize::MAX; words.len() + 1]; lines + 1];
+    let mut breaks = vec![vec![0; words.len() + 1]; lines + 1];
+    longest[0][0] = 0;
+    for k in 1..=lines {
+        for i in k..=words.len() {
+            for j in k - 1..i {
+                if longest[k - 1][j] == usize::MAX {
+                    continue;
+                }
+
+                let candidate = longest[k - 1][j].max(text_length(&words[j..i], unspaced));
+                // ties go to the earlier break, which keeps the bottom line longer
+                if candidate < longest[k][i] {
+                    longest[k][i] = candidate;
+                    breaks[k][i] = j;
+                }
+            }
+        }
+    }
+
+    let mut ends = vec![words.len()];
+    for k in (1..lines).rev() {
+        ends.push(breaks[k + 1][ends[ends.len() - 1]]);
+    }
+    ends.push(0);
+    ends.reverse();
+
+    ends.windows(2)
+        .map(|line| join(&words[line[0]..line[1]], unspaced))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Keeps cues on screen long enough to be read and apart from each other, without running into
+/// the next one.
+fn fit_timing(cues: &mut [Utternace], layout: &SubtitleLayout) {
+    let min_gap = ticks(layout.min_gap);
+    let max_duration = ticks(layout.max_duration);
+
+    for i in 0..cues.len() {
+        let next = cues.get(i + 1).map(|next| next.start);
+        let cue = &mut cues[i];
+
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        let reading_time = (length(&cue.text) as f32 / layout.max_cps * 100.0).ceil() as i64;
+        let limit = next.map_or(i64::MAX, |next| next - min_gap);
+
+        if cue.stop - cue.start < reading_time {
+            cue.stop = (cue.start + reading_time)
+                .min(cue.start + max_duration)
+                .min(limit)
+                .max(cue.stop);
+        }
+
+        if cue.stop > limit {
+            // when the cues are too close together to leave a gap, at least don't overlap
+            cue.stop = if limit > cue.start {
+                limit
+            } else {
+                next.unwrap_or(cue.stop).max(cue.start)
+            };
+        }
+    }
+}
+
+fn join(words: &[Word], unspaced: bool) -> String {
+    words
+        .iter()
+        .map(|word| word.text)
+        .collect::<Vec<_>>()
+        .join(if unspaced { "" } else { " " })
+}
+
+/// Length of `words` when joined on one line.
+fn text_length(words: &[Word], unspaced: bool) -> usize {
+    let spaces = if unspaced {
+        0
+    } else {
+        words.len().saturating_sub(1)
+    };
+    words.iter().map(|word| length(word.text)).sum::<usize>() + spaces
+}
+
+/// How many lines `words` take up when each line is filled before starting the next.
+fn lines_needed(words: &[Word], max_line_length: usize, unspaced: bool) -> usize {
+    let mut lines = 0;
+    let mut line = 0;
+    for word in words {
+        let space = usize::from(line > 0 && !unspaced);
+        if lines == 0 || line + space + length(word.text) > max_line_length {
+            lines += 1;
+            line = length(word.text);
+        } else {
+            line += space + length(word.text);
+        }
+    }
+
+    lines
+}
+
+fn length(text: &str) -> usize {
+    text.chars().filter(|c| *c != '\n').count()
+}
+
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', '”', '’', ')', '»'])
+        .ends_with(['.', '?', '!', '…', '。', '？', '！'])
+}
+
+fn ends_clause(word: &str) -> bool {
+    word.ends_with([',', ';', ':', '，', '、', '；', '：', '-', '—'])
+}
+
+fn ticks(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis() / 10).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Language;
+
+    fn utterance(text: &str, start: i64, stop: i64) -> Utternace {
+        Utternace {
+            start,
+            stop,
+            text: text.to_string(),
+            tokens: Vec::new(),
+            speaker: None,
+            confidence: None,
+            utterance: None,
+        }
+    }
+
+    fn transcript(utterances: Vec<Utternace>, language: Option<Language>) -> Transcript {
+        Transcript {
+            processing_time: Duration::ZERO,
+            utterances,
+            word_utterances: None,
+            language,
+            language_probabilities: None,
+            model: None,
+            parameters: None,
+        }
+    }
+
+    fn texts(cues: &[Utternace]) -> Vec<&str> {
+        cues.iter().map(|cue| cue.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wraps_lines_at_max_line_length() {
+        let transcript = transcript(
+            vec![utterance(
+                "one two three four five six seven eight nine ten eleven",
+                0,
+                500,
+            )],
+            None,
+        );
+        let (cues, _) = lay_out(&transcript, &SubtitleLayout::default());
+
+        assert_eq!(cues.len(), 1);
+        let lines = cues[0].text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.chars().count() <= 42));
+    }
+
+    #[test]
+    fn splits_at_the_end_of_a_sentence() {
+        let layout = SubtitleLayout {
+            max_line_length: 30,
+            max_lines: 1,
+            ..SubtitleLayout::default()
+        };
+        let transcript = transcript(
+            vec![utterance("We are going home now. See you later", 0, 500)],
+            None,
+        );
+        let (cues, _) = lay_out(&transcript, &layout);
+
+        assert_eq!(texts(&cues), ["We are going home now.", "See you later"]);
+    }
+
+    #[test]
+    fn splits_at_the_end_of_a_clause() {
+        let layout = SubtitleLayout {
+            max_line_length: 30,
+            max_lines: 1,
+            ..SubtitleLayout::default()
+        };
+        let transcript = transcript(
+            vec![utterance("We are going home now, see you later", 0, 500)],
+            None,
+        );
+        let (cues, _) = lay_out(&transcript, &layout);
+
+        assert_eq!(texts(&cues), ["We are going home now,", "see you later"]);
+    }
+
+    #[test]
+    fn splits_cues_longer_than_max_duration() {
+        let transcript = transcript(vec![utterance("a b c d e f g h", 0, 2000)], None);
+        let (cues, _) = lay_out(&transcript, &SubtitleLayout::default());
+
+        assert!(cues.len() > 1);
+        assert!(cues.iter().all(|cue| cue.stop - cue.start <= 700));
+        assert_eq!(cues[0].start, 0);
+        assert_eq!(cues[cues.len() - 1].stop, 2000);
+    }
+
+    #[test]
+    fn extends_cues_to_be_read_without_reaching_the_next_one() {
+        let text = "This is a fairly long line of text to read";
+        let (cues, _) = lay_out(
+            &transcript(vec![utterance(text, 0, 100)], None),
+            &SubtitleLayout::default(),
+        );
+        // 42 characters at 17 per second
+        assert_eq!(cues[0].stop, 248);
+
+        let (cues, _) = lay_out(
+            &transcript(
+                vec![utterance(text, 0, 100), utterance("Next", 150, 300)],
+                None,
+            ),
+            &SubtitleLayout::default(),
+        );
+        // stops the minimum gap before the next cue
+        assert_eq!(cues[0].stop, 142);
+        assert_eq!(cues[1].start, 150);
+    }
+
+    #[test]
+    fn lays_out_unspaced_languages_by_character() {
+        let layout = SubtitleLayout {
+            max_line_length: 5,
+            max_lines: 2,
+            ..SubtitleLayout::default()
+        };
+        let transcript = transcript(
+            vec![utterance("これは日本語の文章です", 0, 500)],
+            Some(Language::Japanese),
+        );
+        let (cues, _) = lay_out(&transcript, &layout);
+
+        assert!(cues.len() > 1);
+        assert_eq!(
+            cues.iter()
+                .map(|cue| cue.text.replace('\n', ""))
+                .collect::<String>(),
+            "これは日本語の文章です"
+        );
+        for cue in &cues {
+            assert!(!cue.text.contains(' '));
+            assert!(cue.text.lines().count() <= 2);
+            assert!(cue.text.lines().all(|line| line.chars().count() <= 5));
+        }
+    }
+}
@@ -1,9 +1,43 @@
+mod ass;
+mod callbacks;
+mod checksum;
+mod chunked;
+mod decoder;
+mod diarization;
+mod error;
 mod ffmpeg_decoder;
+mod format;
+mod layout;
+mod live;
 mod model;
+#[cfg(feature = "native-decoder")]
+mod native_decoder;
+mod options;
+mod parse;
+mod resampler;
 mod transcript;
 mod utils;
+mod vad;
 mod whisper;
+mod words;
 
-pub use model::{Model, Size};
-pub use transcript::{Transcript, Utternace};
-pub use whisper::{Language, Whisper};
+pub use ass::{Alignment, AssOptions, AssStyle, AssStyling, Colour};
+pub use callbacks::{Callbacks, CancellationToken};
+pub use checksum::{sha256_file, Checksums};
+pub use chunked::{Chunking, Chunks};
+pub use decoder::read_file as decode_file;
+pub use diarization::{diarize, diarize_by_voice, Diarization};
+pub use error::{DecodeError, Error, Result};
+pub use ffmpeg_decoder::FfmpegError;
+pub use format::{
+    Ass, Csv, Formatter, Json, JsonLines, Lrc, OutputFormat, PlainText, Srt, TimestampedText, Tsv,
+    Ttml, Vtt,
+};
+pub use layout::SubtitleLayout;
+pub use live::{Live, LiveEvent, LiveOptions};
+pub use model::{models_dir, Model, Quantization, Size, Variant, Verification, DEFAULT_MIRROR};
+pub use options::TranscribeOptions;
+pub use transcript::{Parameters, Token, Transcript, Utternace};
+pub use utils::format_timestamp;
+pub use vad::{EnergyVad, VoiceActivityDetector};
+pub use whisper::{Language, LanguageDetection, Whisper};
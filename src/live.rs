@@ -0,0 +1,186 @@
+use crate::{
+    chunked::{duration_to_samples, SAMPLES_PER_TICK},
+    error::{Error, Result},
+    resampler::{downmix, Resampler},
+    transcript::Utternace,
+    whisper::{self, Decoding},
+};
+use std::time::Duration;
+use whisper_rs::WhisperState;
+
+/// Settings for [`crate::Whisper::listen`].
+#[derive(Debug, Clone, Copy)]
+pub struct LiveOptions {
+    /// Sample rate of the incoming audio.
+    pub sample_rate: u32,
+    /// Number of interleaved channels in the incoming audio.
+    pub channels: usize,
+    /// How much new audio to wait for before transcribing the window again.
+    pub step: Duration,
+    /// Maximum length of the window. Once it fills up, its text is finalized.
+    pub length: Duration,
+    /// Audio at the end of a full window that is kept around (and transcribed again) instead of
+    /// being finalized, so words at the edge of the window don't get cut in half.
+    pub keep: Duration,
+}
+
+impl Default for LiveOptions {
+    fn default() -> Self {
+        Self {
+            channels: 1,
+            sample_rate: 16000,
+            step: Duration::from_secs(3),
+            length: Duration::from_secs(15),
+            keep: Duration::from_secs(2),
+        }
+    }
+}
+
+impl LiveOptions {
+    /// Checks that audio can be transcribed with these options, which [`crate::Whisper::listen`]
+    /// does before starting.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: &str| Err(Error::InvalidOptions(message.to_string()));
+
+        if self.sample_rate == 0 {
+            return invalid("the sample rate has to be above 0");
+        }
+        if self.channels == 0 {
+            return invalid("there has to be at least one channel");
+        }
+        if duration_to_samples(self.length) == 0 {
+            return invalid("the window has to be at least a millisecond long");
+        }
+        if self.keep >= self.length {
+            return invalid("the audio kept from a full window has to be shorter than the window");
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum LiveEvent {
+    /// The current best guess for the audio that hasn't been finalized yet. Replaces any previous
+    /// provisional text.
+    Provisional(String),
+    /// An utterance that won't change anymore, timestamped from the start of the stream.
+    Final(Utternace),
+}
+
+/// A sliding-window transcriber for audio that arrives in real time.
+pub struct Live {
+    options: LiveOptions,
+    state: WhisperState,
+    resampler: Resampler,
+    decoding: Decoding,
+    window: Vec<f32>,
+    offset: usize,
+    unprocessed: usize,
+}
+
+impl Live {
+    pub(crate) fn new(state: WhisperState, options: LiveOptions, decoding: Decoding) -> Self {
+        Self {
+            state,
+            options,
+            offset: 0,
+            unprocessed: 0,
+            window: Vec::new(),
+            decoding,
+            resampler: Resampler::new(options.sample_rate, 16000),
+        }
+    }
+
+    /// Feeds interleaved samples into the window, transcribing it again if enough new audio has
+    /// arrived since the last run.
+    pub fn push(&mut self, samples: &[f32]) -> Result<Vec<LiveEvent>> {
+        let samples = self
+            .resampler
+            .process(&downmix(samples, self.options.channels));
+
+        self.unprocessed += samples.len();
+        self.window.extend(samples);
+
+        if self.unprocessed < duration_to_samples(self.options.step) {
+            return Ok(Vec::new());
+        }
+
+        self.process(false)
+    }
+
+    /// Transcribes and finalizes whatever audio is left in the window.
+    pub fn finish(&mut self) -> Result<Vec<LiveEvent>> {
+        let samples = self.resampler.flush();
+        self.window.extend(samples);
+
+        // what's left can be longer than a window, when the last push didn't make it run
+        let mut events = Vec::new();
+        while !self.window.is_empty() {
+            events.extend(self.process(true)?);
+        }
+
+        Ok(events)
+    }
+
+    fn process(&mut self, flush: bool) -> Result<Vec<LiveEvent>> {
+        self.unprocessed = 0;
+        if self.window.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let length = duration_to_samples(self.options.length).min(self.window.len());
+        // every window is transcribed on its own, since previous ones may be transcribed again
+        let mut params = self.decoding.params();
+        params.set_no_context(true);
+        self.state.full(params, &self.window[..length])?;
+        let segments = whisper::segments(&self.state, false, false)?;
+
+        if !flush && self.window.len() < duration_to_samples(self.options.length) {
+            let text = segments.iter().map(|s| s.text.trim()).collect::<Vec<_>>();
+            return Ok(vec![LiveEvent::Provisional(text.join(" "))]);
+        }
+
+        let boundary = if flush {
+            length
+        } else {
+            length.saturating_sub(duration_to_samples(self.options.keep))
+        };
+
+        #[allow(clippy::cast_possible_wrap)]
+        let offset = (self.offset / SAMPLES_PER_TICK) as i64;
+
+        let mut events = Vec::new();
+        let mut consumed = 0;
+        let mut provisional = Vec::new();
+        for mut utterance in segments {
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+            let stop_sample = utterance.stop.max(0) as usize * SAMPLES_PER_TICK;
+            if !flush && (stop_sample > boundary || !provisional.is_empty()) {
+                provisional.push(utterance.text.trim().to_string());
+                continue;
+            }
+
+            utterance.start += offset;
+            utterance.stop += offset;
+            events.push(LiveEvent::Final(utterance));
+            consumed = stop_sample;
+        }
+
+        if flush {
+            consumed = length;
+        } else if consumed == 0 {
+            consumed = (boundary - boundary % SAMPLES_PER_TICK).max(SAMPLES_PER_TICK);
+        }
+
+        let consumed = consumed.min(self.window.len());
+        self.window.drain(..consumed);
+        self.offset += consumed;
+
+        if !flush {
+            events.push(LiveEvent::Provisional(provisional.join(" ")));
+        }
+
+        Ok(events)
+    }
+}
@@ -1,75 +1,172 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
-use clap::Parser;
-use std::path::Path;
-use utils::write_to;
+use anyhow::bail;
+use clap::{Parser, Subcommand};
+use std::{io, path::PathBuf, process::ExitCode};
 
-mod utils;
+mod commands;
 
-use whisper_cli::{Language, Model, Size, Whisper};
+use whisper_cli::{Error, Language, Model, Variant, Whisper};
 
 #[derive(Parser)]
 #[command(
     author,
     version,
     about = "Locally transcribe audio files, using Whisper.",
-    long_about = "Generate a transcript of an audio file using the Whisper speech-to-text engine. The transcript will be saved as a .txt, .vtt, and .srt file in the same directory as the audio file."
+    long_about = "Generate a transcript of one or more audio files using the Whisper speech-to-text engine. By default, each transcript will be saved as a .txt, .vtt, .srt and .json file in the same directory as its audio file.",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    transcribe: commands::transcribe::Args,
+}
+
+#[derive(clap::Args)]
+struct ModelArgs {
     /// Name of the Whisper model to use
     #[clap(short, long, default_value = "medium")]
-    model: Size,
+    model: Variant,
+
+    /// Use a local ggml model file instead, like a fine-tuned or distilled model
+    #[clap(long, value_name = "FILE", conflicts_with = "model")]
+    model_path: Option<PathBuf>,
+
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+/// Where models get downloaded from. Global, so they can also be passed after the subcommands of
+/// `models`.
+#[derive(clap::Args)]
+struct SourceArgs {
+    /// Base URL to download models from, instead of Hugging Face
+    #[clap(long, global = true, env = "WHISPER_MODEL_MIRROR", value_name = "URL")]
+    mirror: Option<String>,
+
+    /// Fail instead of downloading models that aren't downloaded yet
+    #[clap(long, global = true, env = "WHISPER_OFFLINE", default_value = "false")]
+    offline: bool,
+
+    /// Download models even when there's no checksum to check them against, like from mirrors
+    /// that don't report one
+    #[clap(
+        long,
+        global = true,
+        env = "WHISPER_ALLOW_UNVERIFIED",
+        default_value = "false"
+    )]
+    allow_unverified: bool,
+}
+
+impl SourceArgs {
+    fn apply(&self, mut model: Model) -> Model {
+        if let Some(mirror) = &self.mirror {
+            model = model.with_mirror(mirror);
+        }
 
-    /// Language spoken in the audio. Attempts to auto-detect by default.
-    #[clap(short, long)]
-    lang: Option<Language>,
+        model
+            .offline(self.offline)
+            .allow_unverified(self.allow_unverified)
+            .on_lock_wait(|path| {
+                eprintln!(
+                    "Waiting for another process to release {}...",
+                    path.display()
+                );
+            })
+    }
+}
+
+impl ModelArgs {
+    fn model(&self) -> Model {
+        self.source.apply(
+            self.model_path
+                .as_ref()
+                .map_or_else(|| Model::new(self.model), Model::from_path),
+        )
+    }
+
+    async fn load(&self, lang: Option<Language>) -> anyhow::Result<Whisper> {
+        self.load_with(lang, false).await
+    }
 
-    /// Path to the audio file to transcribe
-    audio: String,
+    /// Loads the model, set up to align words with DTW when `dtw` is true.
+    async fn load_with(&self, lang: Option<Language>, dtw: bool) -> anyhow::Result<Whisper> {
+        let english = matches!(lang, None | Some(Language::Auto | Language::English));
+        if self.model_path.is_none() && self.model.is_english_only() && !english {
+            bail!("The selected model only supports English.");
+        }
 
-    /// Toggle translation
-    #[clap(short, long, default_value = "false")]
-    translate: bool,
+        let whisper = if dtw {
+            Whisper::new_with_dtw(self.model(), lang).await?
+        } else {
+            Whisper::new(self.model(), lang).await?
+        };
+        if !whisper.is_multilingual() && !english {
+            bail!("The selected model only supports English.");
+        }
 
-    /// Generate timestamps for each word
-    #[clap(short, long, default_value = "false")]
-    karaoke: bool,
+        Ok(whisper)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Transcribe raw PCM audio from stdin or a named pipe in real time
+    Listen(commands::listen::Args),
+    /// Detect the language spoken in audio files, without transcribing them
+    DetectLanguage(commands::detect_language::Args),
+    /// Manage the downloaded Whisper models
+    Models(commands::models::Args),
+    /// Serve transcriptions over HTTP, with an API compatible with OpenAI's
+    Serve(commands::serve::Args),
 }
 
 #[tokio::main]
-async fn main() {
-    let mut args = Args::parse();
-    let audio = Path::new(&args.audio);
-    let file_name = audio.file_name().unwrap().to_str().unwrap();
+async fn main() -> ExitCode {
+    let args = Args::parse();
 
-    assert!(audio.exists(), "The provided audio file does not exist.");
+    let result = match args.command {
+        Some(Command::Listen(listen)) => commands::listen::run(listen).await,
+        Some(Command::DetectLanguage(detect)) => commands::detect_language::run(detect).await,
+        Some(Command::Models(models)) => commands::models::run(models).await,
+        Some(Command::Serve(serve)) => commands::serve::run(serve).await,
+        None => commands::transcribe::run(args.transcribe).await,
+    };
 
-    if args.model.is_english_only() && (args.lang == Some(Language::Auto) || args.lang.is_none()) {
-        args.lang = Some(Language::English);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            exit_code(&e)
+        }
     }
+}
+
+/// Maps each kind of failure to its own exit code, so scripts can tell them apart.
+fn exit_code(error: &anyhow::Error) -> ExitCode {
+    let code = match error.downcast_ref::<Error>() {
+        Some(
+            Error::Download { .. } | Error::ChecksumMismatch { .. } | Error::UnknownChecksum { .. },
+        ) => 3,
+        Some(
+            Error::ModelNotFound { .. } | Error::ModelLoad { .. } | Error::NoAlignmentHeads(_),
+        ) => 4,
+        Some(Error::Decode(_)) => 5,
+        Some(Error::Inference(_) | Error::NoSpeech) => 6,
+        Some(Error::Io(_)) => 7,
+        None if error.downcast_ref::<io::Error>().is_some() => 7,
+        Some(
+            Error::InvalidToken(_)
+            | Error::Cancelled
+            | Error::InvalidOptions(_)
+            | Error::InvalidTranscript(_),
+        )
+        | None => 1,
+    };
 
-    assert!(
-        !args.model.is_english_only() || args.lang == Some(Language::English),
-        "The selected model only supports English."
-    );
-
-    let mut whisper = Whisper::new(Model::new(args.model), args.lang).await;
-    let transcript = whisper
-        .transcribe(audio, args.translate, args.karaoke)
-        .unwrap();
-
-    write_to(
-        audio.with_file_name(format!("{file_name}.txt")),
-        &transcript.as_text(),
-    );
-    write_to(
-        audio.with_file_name(format!("{file_name}.vtt")),
-        &transcript.as_vtt(),
-    );
-    write_to(
-        audio.with_file_name(format!("{file_name}.srt")),
-        &transcript.as_srt(),
-    );
-
-    println!("time: {:?}", transcript.processing_time);
+    ExitCode::from(code)
 }
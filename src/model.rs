@@ -1,8 +1,46 @@
-use crate::utils::download_file;
+use crate::{
+    checksum::{sha256_file, Checksums},
+    error::{Error, Result},
+    utils::{download_file, fetch_checksum, lock_file, with_suffix},
+};
+use clap::{builder::PossibleValue, ValueEnum};
 use dirs::cache_dir;
-use std::{fmt::Display, fs, path::PathBuf};
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+/// Name of the checksum table kept next to the downloaded models.
+const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// The checksums the published models are known to have.
+const PUBLISHED_CHECKSUMS: &str = include_str!("models.sha256");
+
+/// The directory models are downloaded to.
+pub fn models_dir() -> Result<PathBuf> {
+    let mut path = cache_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not find cache directory"))?;
+    path.push("whisper");
+    path.push("models");
+
+    Ok(path)
+}
+
+/// The outcome of [`Model::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// The file matches its known checksum.
+    Valid,
+    /// The file doesn't match its known checksum, so it's corrupted or incomplete.
+    Mismatch { expected: String, actual: String },
+    /// There is no known checksum for the file to compare against.
+    Unknown { actual: String },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
 pub enum Size {
     #[clap(name = "tiny.en")]
     TinyEnglish,
@@ -24,24 +62,50 @@ pub enum Size {
     Large,
     #[clap(name = "large-v1")]
     LargeV1,
+    #[clap(name = "large-v2")]
+    LargeV2,
+    #[clap(name = "large-v3")]
+    LargeV3,
+    #[clap(name = "large-v3-turbo")]
+    LargeV3Turbo,
+    #[clap(name = "distil-medium.en")]
+    DistilMediumEnglish,
+    #[clap(name = "distil-large-v2")]
+    DistilLargeV2,
+    #[clap(name = "distil-large-v3")]
+    DistilLargeV3,
 }
 
 impl Size {
-    pub fn get_path(self) -> PathBuf {
-        let mut path = cache_dir().expect("Could not find cache directory");
-        path.push("whisper");
-        path.push("models");
-        path.push(format!("{self}.bin"));
+    pub fn get_path(self) -> Result<PathBuf> {
+        Variant::from(self).get_path()
+    }
 
-        path
+    /// Name of the model's file in [`models_dir`].
+    pub fn file_name(self) -> String {
+        Variant::from(self).file_name()
     }
 
     pub const fn is_english_only(self) -> bool {
         matches!(
             self,
-            Self::TinyEnglish | Self::BaseEnglish | Self::SmallEnglish | Self::MediumEnglish
+            Self::TinyEnglish
+                | Self::BaseEnglish
+                | Self::SmallEnglish
+                | Self::MediumEnglish
+                | Self::DistilMediumEnglish
+                | Self::DistilLargeV2
+                | Self::DistilLargeV3
         )
     }
+
+    /// Returns the variant of this model with its weights quantized.
+    pub const fn quantized(self, quantization: Quantization) -> Variant {
+        Variant {
+            size: self,
+            quantization: Some(quantization),
+        }
+    }
 }
 
 impl Display for Size {
@@ -57,43 +121,406 @@ impl Display for Size {
             Self::Medium => "medium",
             Self::Large => "large",
             Self::LargeV1 => "large-v1",
+            Self::LargeV2 => "large-v2",
+            Self::LargeV3 => "large-v3",
+            Self::LargeV3Turbo => "large-v3-turbo",
+            Self::DistilMediumEnglish => "distil-medium.en",
+            Self::DistilLargeV2 => "distil-large-v2",
+            Self::DistilLargeV3 => "distil-large-v3",
+        };
+
+        write!(f, "{key}")
+    }
+}
+
+/// How a model's weights are quantized. Quantized models are smaller and run faster (especially
+/// on CPUs), at a small cost in accuracy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Quantization {
+    #[clap(name = "q5_0")]
+    Q5_0,
+    #[clap(name = "q5_1")]
+    Q5_1,
+    #[clap(name = "q8_0")]
+    Q8_0,
+}
+
+impl Display for Quantization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = match self {
+            Self::Q5_0 => "q5_0",
+            Self::Q5_1 => "q5_1",
+            Self::Q8_0 => "q8_0",
         };
 
         write!(f, "{key}")
     }
 }
 
+/// A model size, optionally quantized. Displayed (and parsed) the way whisper.cpp names its model
+/// files, like `large-v3-turbo-q5_0`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Variant {
+    pub size: Size,
+    pub quantization: Option<Quantization>,
+}
+
+impl Variant {
+    /// Every variant published by the whisper.cpp and distil-whisper projects.
+    pub const PUBLISHED: &'static [Self] = &[
+        Self::full(Size::TinyEnglish),
+        Size::TinyEnglish.quantized(Quantization::Q5_1),
+        Self::full(Size::Tiny),
+        Size::Tiny.quantized(Quantization::Q5_1),
+        Size::Tiny.quantized(Quantization::Q8_0),
+        Self::full(Size::BaseEnglish),
+        Size::BaseEnglish.quantized(Quantization::Q5_1),
+        Self::full(Size::Base),
+        Size::Base.quantized(Quantization::Q5_1),
+        Size::Base.quantized(Quantization::Q8_0),
+        Self::full(Size::SmallEnglish),
+        Size::SmallEnglish.quantized(Quantization::Q5_1),
+        Self::full(Size::Small),
+        Size::Small.quantized(Quantization::Q5_1),
+        Size::Small.quantized(Quantization::Q8_0),
+        Self::full(Size::MediumEnglish),
+        Size::MediumEnglish.quantized(Quantization::Q5_0),
+        Self::full(Size::Medium),
+        Size::Medium.quantized(Quantization::Q5_0),
+        Size::Medium.quantized(Quantization::Q8_0),
+        Self::full(Size::Large),
+        Self::full(Size::LargeV1),
+        Self::full(Size::LargeV2),
+        Size::LargeV2.quantized(Quantization::Q5_0),
+        Size::LargeV2.quantized(Quantization::Q8_0),
+        Self::full(Size::LargeV3),
+        Size::LargeV3.quantized(Quantization::Q5_0),
+        Self::full(Size::LargeV3Turbo),
+        Size::LargeV3Turbo.quantized(Quantization::Q5_0),
+        Size::LargeV3Turbo.quantized(Quantization::Q8_0),
+        Self::full(Size::DistilMediumEnglish),
+        Self::full(Size::DistilLargeV2),
+        Self::full(Size::DistilLargeV3),
+    ];
+
+    /// The model with its original (unquantized) weights.
+    pub const fn full(size: Size) -> Self {
+        Self {
+            size,
+            quantization: None,
+        }
+    }
+
+    pub fn get_path(self) -> Result<PathBuf> {
+        Ok(models_dir()?.join(self.file_name()))
+    }
+
+    /// Name of the model's file in [`models_dir`].
+    pub fn file_name(self) -> String {
+        format!("{self}.bin")
+    }
+
+    pub const fn is_english_only(self) -> bool {
+        self.size.is_english_only()
+    }
+
+    /// The SHA-256 checksum the model was published with, if it's known.
+    pub fn published_checksum(self) -> Option<String> {
+        Checksums::parse(PUBLISHED_CHECKSUMS)
+            .get(&format!("ggml-{self}.bin"))
+            .map(ToString::to_string)
+    }
+
+    /// Where to download the model from. Mirrors are expected to serve every model as
+    /// `ggml-{variant}.bin`.
+    fn url(self, mirror: Option<&str>) -> String {
+        if let Some(mirror) = mirror {
+            return format!("{}/ggml-{self}.bin", mirror.trim_end_matches('/'));
+        }
+
+        // distilled models are published by their authors, under their own names
+        match (self.size, self.quantization) {
+            (Size::DistilMediumEnglish, None) => {
+                "https://huggingface.co/distil-whisper/distil-medium.en/resolve/main/ggml-medium-32-2.en.bin".to_string()
+            }
+            (Size::DistilLargeV2, None) => {
+                "https://huggingface.co/distil-whisper/distil-large-v2/resolve/main/ggml-large-32-2.en.bin".to_string()
+            }
+            (Size::DistilLargeV3, None) => {
+                "https://huggingface.co/distil-whisper/distil-large-v3-ggml/resolve/main/ggml-distil-large-v3.bin".to_string()
+            }
+            _ => format!("{DEFAULT_MIRROR}/ggml-{self}.bin"),
+        }
+    }
+}
+
+impl From<Size> for Variant {
+    fn from(size: Size) -> Self {
+        Self::full(size)
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.quantization {
+            Some(quantization) => write!(f, "{}-{quantization}", self.size),
+            None => write!(f, "{}", self.size),
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (size, quantization) = match s.rsplit_once('-') {
+            Some((size, quantization)) if quantization.starts_with('q') => {
+                (size, Some(Quantization::from_str(quantization, true)?))
+            }
+            _ => (s, None),
+        };
+
+        Ok(Self {
+            quantization,
+            size: Size::from_str(size, true)?,
+        })
+    }
+}
+
+/// Only the published variants can be picked from the command line.
+impl ValueEnum for Variant {
+    fn value_variants<'a>() -> &'a [Self] {
+        Self::PUBLISHED
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.to_string()))
+    }
+}
+
+/// Where models are downloaded from by default. Mirrors need to serve files with the same names.
+pub const DEFAULT_MIRROR: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+#[derive(Debug, Clone)]
+enum Source {
+    Variant(Variant),
+    Path(PathBuf),
+}
+
+#[derive(Debug, Clone)]
 pub struct Model {
-    size: Size,
+    source: Source,
+    mirror: Option<String>,
+    offline: bool,
+    unverified: bool,
+    on_lock_wait: Option<fn(&Path)>,
 }
 
 impl Model {
-    pub const fn new(size: Size) -> Self {
-        Self { size }
+    pub fn new<V: Into<Variant>>(variant: V) -> Self {
+        Self {
+            offline: false,
+            unverified: false,
+            on_lock_wait: None,
+            mirror: None,
+            source: Source::Variant(variant.into()),
+        }
     }
 
-    pub fn get_path(&self) -> PathBuf {
-        self.size.get_path()
+    /// A model stored in a local ggml file, like a fine-tuned or distilled one. It is used as-is,
+    /// and never downloaded.
+    pub fn from_path<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            offline: false,
+            unverified: false,
+            on_lock_wait: None,
+            mirror: None,
+            source: Source::Path(path.into()),
+        }
     }
 
-    pub async fn download(&self) {
-        let path = self.get_path();
-        if path.exists() {
-            return;
+    /// Downloads the model from `url` instead of [`DEFAULT_MIRROR`]. The model is expected at
+    /// `{url}/ggml-{variant}.bin`.
+    #[must_use]
+    pub fn with_mirror<S: Into<String>>(mut self, url: S) -> Self {
+        self.mirror = Some(url.into());
+        self
+    }
+
+    /// Fails instead of downloading the model, if it hasn't been downloaded yet.
+    #[must_use]
+    pub const fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Downloads the model even when there's no checksum to check it against, because the server
+    /// doesn't report one and none is built in. Without this, those downloads fail with
+    /// [`Error::UnknownChecksum`].
+    #[must_use]
+    pub const fn allow_unverified(mut self, unverified: bool) -> Self {
+        self.unverified = unverified;
+        self
+    }
+
+    /// Calls `callback` with the path of the lock file when another process is already
+    /// downloading the model, before waiting for it to finish.
+    #[must_use]
+    pub const fn on_lock_wait(mut self, callback: fn(&Path)) -> Self {
+        self.on_lock_wait = Some(callback);
+        self
+    }
+
+    /// The size of the model, unless it's a custom one created with [`Model::from_path`].
+    pub const fn size(&self) -> Option<Size> {
+        match self.source {
+            Source::Variant(variant) => Some(variant.size),
+            Source::Path(_) => None,
         }
+    }
 
-        let cache_dir = path.parent().expect("Failed to get cache dir");
-        if !cache_dir.exists() {
-            fs::create_dir_all(cache_dir).expect("Failed to create cache dir.");
+    /// The variant of the model, unless it's a custom one created with [`Model::from_path`].
+    pub const fn variant(&self) -> Option<Variant> {
+        match self.source {
+            Source::Variant(variant) => Some(variant),
+            Source::Path(_) => None,
         }
+    }
 
-        download_file(
-            &format!(
-                "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-{}.bin",
-                self.size
+    /// The name of the model, which is the name of its file for custom ones.
+    pub fn name(&self) -> String {
+        match &self.source {
+            Source::Variant(variant) => variant.to_string(),
+            Source::Path(path) => path.file_stem().map_or_else(
+                || path.display().to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
             ),
-            path.to_str().unwrap(),
-        )
-        .await;
+        }
+    }
+
+    pub fn get_path(&self) -> Result<PathBuf> {
+        match &self.source {
+            Source::Variant(variant) => variant.get_path(),
+            Source::Path(path) => Ok(path.clone()),
+        }
+    }
+
+    pub fn is_downloaded(&self) -> Result<bool> {
+        Ok(self.get_path()?.exists())
+    }
+
+    /// Downloads the model, unless it has been downloaded already.
+    ///
+    /// Interrupted downloads are resumed, and the file is checked against the checksum published
+    /// by the server before being used. That checksum is also recorded, so the file can be checked
+    /// again later with [`Model::verify`]. Only one process downloads a given model at a time.
+    pub async fn download(&self) -> Result<()> {
+        let path = self.get_path()?;
+        if path.exists() {
+            return Ok(());
+        }
+
+        let Source::Variant(variant) = self.source else {
+            return Err(Error::ModelNotFound { path });
+        };
+
+        if self.offline {
+            return Err(Error::ModelNotFound { path });
+        }
+
+        if let Some(cache_dir) = path.parent() {
+            fs::create_dir_all(cache_dir)?;
+        }
+
+        let _lock = lock_file(&with_suffix(&path, ".lock"), self.on_lock_wait).await?;
+        // another process might have downloaded it while we were waiting for the lock
+        if path.exists() {
+            return Ok(());
+        }
+
+        let url = variant.url(self.mirror.as_deref());
+        let expected = match fetch_checksum(&url).await {
+            Some(expected) => Some(expected),
+            None => variant.published_checksum(),
+        };
+        if expected.is_none() && !self.unverified {
+            return Err(Error::UnknownChecksum { url });
+        }
+        let hash = download_file(&url, &path, expected.as_deref()).await?;
+
+        let mut checksums = Self::checksums()?;
+        checksums.insert(variant.file_name(), hash);
+        checksums.save(models_dir()?.join(CHECKSUMS_FILE))?;
+
+        Ok(())
+    }
+
+    /// Deletes the model's file. Returns whether there was anything to delete.
+    pub fn remove(&self) -> Result<bool> {
+        match fs::remove_file(self.get_path()?) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut checksums = Self::checksums()?;
+        if let Some(variant) = self.variant() {
+            if checksums.remove(&variant.file_name()).is_some() {
+                checksums.save(models_dir()?.join(CHECKSUMS_FILE))?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Checks the model's file against `checksums` if provided, or else against the checksum the
+    /// model was published with, falling back to the one recorded when it was downloaded.
+    pub fn verify(&self, checksums: Option<&Checksums>) -> Result<Verification> {
+        let path = self.get_path()?;
+        let actual = sha256_file(&path)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let expected = match (
+            checksums,
+            self.variant().and_then(Variant::published_checksum),
+        ) {
+            // also accept tables using the names the files are published under
+            (Some(checksums), _) => checksums
+                .get(&name)
+                .or_else(|| checksums.get(&format!("ggml-{name}")))
+                .map(ToString::to_string),
+            (None, Some(published)) => Some(published),
+            (None, None) => Self::checksums()?.get(&name).map(ToString::to_string),
+        };
+
+        Ok(match expected {
+            None => Verification::Unknown { actual },
+            Some(expected) if expected == actual => Verification::Valid,
+            Some(expected) => Verification::Mismatch { actual, expected },
+        })
+    }
+
+    /// The checksums of the downloaded models.
+    pub fn checksums() -> Result<Checksums> {
+        Ok(Checksums::load(models_dir()?.join(CHECKSUMS_FILE))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_published_variant_has_a_checksum() {
+        let missing = Variant::PUBLISHED
+            .iter()
+            .filter(|variant| variant.published_checksum().is_none())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert!(missing.is_empty(), "no published checksum for {missing:?}");
     }
 }
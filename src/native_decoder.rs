@@ -0,0 +1,170 @@
+use crate::resampler::{deinterleave, downmix, Resampler};
+use std::{fs::File, io::ErrorKind, path::Path};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL},
+    errors::Error,
+    formats::{FormatOptions, FormatReader},
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+/// Decodes and resamples an audio file to 16kHz, one packet at a time.
+pub struct NativeStream {
+    track_id: u32,
+    finished: bool,
+    channels: usize,
+    resamplers: Vec<Resampler>,
+    decoder: Box<dyn Decoder>,
+    format: Box<dyn FormatReader>,
+}
+
+impl NativeStream {
+    /// Decodes the file as mono.
+    pub fn new<P: AsRef<Path>>(audio_file_path: P) -> Result<Self, Error> {
+        Self::with_channels(audio_file_path, 1)
+    }
+
+    /// Decodes the file into `channels` interleaved channels. Mono output is a downmix of every
+    /// channel, otherwise the first channels of the file are kept (and mono files are duplicated).
+    pub fn with_channels<P: AsRef<Path>>(
+        audio_file_path: P,
+        channels: usize,
+    ) -> Result<Self, Error> {
+        let path = audio_file_path.as_ref();
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let stream = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+        let format = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )?
+            .format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(Error::Unsupported("no audio track found"))?;
+
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .filter(|rate| *rate > 0)
+            .ok_or(Error::Unsupported("unknown sample rate"))?;
+
+        Ok(Self {
+            track_id: track.id,
+            channels,
+            finished: false,
+            resamplers: (0..channels)
+                .map(|_| Resampler::new(sample_rate, 16000))
+                .collect(),
+            decoder: symphonia::default::get_codecs()
+                .make(&track.codec_params, &DecoderOptions::default())?,
+            format,
+        })
+    }
+
+    fn read_chunk(&mut self) -> Result<Option<Vec<f32>>, Error> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            let spec = *decoded.spec();
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+
+            let source_channels = spec.channels.count().max(1);
+            if self.channels == 1 {
+                return Ok(Some(
+                    self.resamplers[0].process(&downmix(buffer.samples(), source_channels)),
+                ));
+            }
+
+            let source = deinterleave(buffer.samples(), source_channels);
+            let resampled = self
+                .resamplers
+                .iter_mut()
+                .enumerate()
+                .map(|(channel, resampler)| {
+                    resampler.process(&source[channel.min(source_channels - 1)])
+                })
+                .collect::<Vec<_>>();
+
+            return Ok(Some(interleave(&resampled)));
+        }
+    }
+
+    fn flush(&mut self) -> Vec<f32> {
+        let flushed = self
+            .resamplers
+            .iter_mut()
+            .map(Resampler::flush)
+            .collect::<Vec<_>>();
+
+        interleave(&flushed)
+    }
+}
+
+impl Iterator for NativeStream {
+    type Item = Result<Vec<f32>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.read_chunk() {
+            Ok(Some(samples)) => Some(Ok(samples)),
+            Ok(None) => {
+                self.finished = true;
+                Some(Ok(self.flush()))
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+pub fn read_file<P: AsRef<Path>>(audio_file_path: P, channels: usize) -> Result<Vec<f32>, Error> {
+    let mut samples = Vec::new();
+    for chunk in NativeStream::with_channels(audio_file_path, channels)? {
+        samples.extend(chunk?);
+    }
+
+    Ok(samples)
+}
+
+fn interleave(channels: &[Vec<f32>]) -> Vec<f32> {
+    let frames = channels.iter().map(Vec::len).min().unwrap_or_default();
+
+    (0..frames)
+        .flat_map(|frame| channels.iter().map(move |channel| channel[frame]))
+        .collect()
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// How whisper decodes audio into text.
+///
+/// The defaults match whisper.cpp's own, except for `best_of`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranscribeOptions {
+    /// Translate the transcript to English.
+    pub translate: bool,
+    /// Compute timestamps for each word.
+    pub word_timestamps: bool,
+    /// Use beam search with this many beams, instead of greedy decoding.
+    pub beam_size: Option<usize>,
+    /// Beam search patience factor, as described in <https://arxiv.org/abs/2204.05424>.
+    pub patience: Option<f32>,
+    /// Number of candidates to pick the best from when sampling with a non-zero temperature.
+    pub best_of: usize,
+    /// Sampling temperature. Zero always picks the most likely token.
+    pub temperature: f32,
+    /// How much to raise the temperature when decoding fails one of the thresholds below, to try
+    /// again. Zero disables the fallback.
+    pub temperature_increment: f32,
+    /// Decoding is retried if the entropy of the generated tokens is higher than this, which
+    /// usually means the text got stuck repeating itself.
+    pub entropy_threshold: f32,
+    /// Decoding is retried if the average log probability of the generated tokens is lower than
+    /// this.
+    pub logprob_threshold: f32,
+    /// Segments whose probability of containing no speech is higher than this are skipped.
+    pub no_speech_threshold: f32,
+    /// Text to condition the first window on, like the vocabulary or spelling to expect.
+    pub initial_prompt: Option<String>,
+    /// Ids of tokens that should never be generated. `-1` suppresses the non-speech symbols.
+    pub suppress_tokens: Vec<i32>,
+    /// Split segments so they are at most this many characters long, on word boundaries.
+    pub max_segment_length: Option<usize>,
+}
+
+impl Default for TranscribeOptions {
+    fn default() -> Self {
+        Self {
+            best_of: 1,
+            patience: None,
+            beam_size: None,
+            translate: false,
+            temperature: 0.0,
+            initial_prompt: None,
+            word_timestamps: false,
+            entropy_threshold: 2.4,
+            logprob_threshold: -1.0,
+            no_speech_threshold: 0.6,
+            max_segment_length: None,
+            temperature_increment: 0.4,
+            suppress_tokens: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use crate::{
+    error::{Error, Result},
+    transcript::{Transcript, Utternace},
+};
+use std::{fs, path::Path, time::Duration};
+
+impl Transcript {
+    /// Loads a transcript saved as SRT, WebVTT or JSON, going by the extension of `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("srt") => Self::from_srt(&contents),
+            Some(ext) if ext.eq_ignore_ascii_case("vtt") => Self::from_vtt(&contents),
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::from_json(&contents),
+            _ => Err(Error::InvalidTranscript(format!(
+                "{} isn't an SRT, WebVTT or JSON file",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Parses a transcript saved by [`Transcript::as_json`], with everything it had.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut transcript: Self =
+            serde_json::from_str(json).map_err(|e| Error::InvalidTranscript(e.to_string()))?;
+
+        // transcripts saved before words recorded their utterance only have their timing to go by
+        if let Some(words) = &mut transcript.word_utterances {
+            for word in words.iter_mut().filter(|word| word.utterance.is_none()) {
+                word.utterance = transcript
+                    .utterances
+                    .partition_point(|utterance| utterance.start <= word.start)
+                    .checked_sub(1);
+            }
+        }
+
+        Ok(transcript)
+    }
+
+    /// Parses SubRip subtitles into a transcript with one utterance per cue. Formatting tags are
+    /// dropped, and `Speaker 1: ` prefixes are read back as speakers.
+    pub fn from_srt(srt: &str) -> Result<Self> {
+        let mut utterances = Vec::new();
+
+        for block in blocks(srt) {
+            let (start, stop, text) = cue(&block)?;
+            let text = strip_tags(&text.join(" "));
+            let (speaker, text) = text
+                .split_once(": ")
+                .and_then(|(label, rest)| Some((parse_speaker(label)?, rest.to_string())))
+                .map_or((None, text.clone()), |(speaker, text)| {
+                    (Some(speaker), text)
+                });
+
+            utterances.push(Utternace {
+                start,
+                stop,
+                text,
+                speaker,
+                tokens: Vec::new(),
+                confidence: None,
+                utterance: None,
+            });
+        }
+
+        Ok(Self::from_utterances(utterances, None))
+    }
+
+    /// Parses WebVTT subtitles into a transcript with one utterance per cue. `<v Speaker 1>` voice
+    /// spans are read back as speakers, and inline timestamps (like the ones rendered with word
+    /// timestamps) as words.
+    pub fn from_vtt(vtt: &str) -> Result<Self> {
+        let mut blocks = blocks(vtt.trim_start_matches('\u{feff}'));
+        if !blocks
+            .first()
+            .is_some_and(|header| header[0].1.starts_with("WEBVTT"))
+        {
+            return Err(Error::InvalidTranscript(
+                "WebVTT files have to start with WEBVTT".to_string(),
+            ));
+        }
+        blocks.remove(0);
+
+        let mut utterances = Vec::new();
+        let mut words = Vec::new();
+        let mut timed = false;
+        for block in blocks {
+            let first = block[0].1;
+            if ["NOTE", "STYLE", "REGION"]
+                .iter()
+                .any(|keyword| first.starts_with(keyword) && !first.contains("-->"))
+            {
+                continue;
+            }
+
+            let (start, stop, text) = cue(&block)?;
+            let payload = Payload::parse(&text.join("\n"), start);
+
+            // a cue with a single word has no inline timestamps, only the span around the word
+            if payload.timed || payload.spans {
+                words.extend(payload.words(stop, utterances.len()));
+            }
+            timed |= payload.timed;
+
+            utterances.push(Utternace {
+                start,
+                stop,
+                text: payload.text(),
+                speaker: payload.speaker,
+                tokens: Vec::new(),
+                confidence: None,
+                utterance: None,
+            });
+        }
+
+        let words = (timed && !words.is_empty()).then_some(words);
+        Ok(Self::from_utterances(utterances, words))
+    }
+
+    fn from_utterances(
+        utterances: Vec<Utternace>,
+        word_utterances: Option<Vec<Utternace>>,
+    ) -> Self {
+        Self {
+            utterances,
+            word_utterances,
+            model: None,
+            language: None,
+            parameters: None,
+            language_probabilities: None,
+            processing_time: Duration::ZERO,
+        }
+    }
+}
+
+/// The non-empty lines of `text` with their line numbers, grouped by the blank lines between them.
+fn blocks(text: &str) -> Vec<Vec<(usize, &str)>> {
+    let mut blocks = vec![Vec::new()];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blocks.push(Vec::new());
+        } else if let Some(block) = blocks.last_mut() {
+            block.push((i + 1, line));
+        }
+    }
+
+    blocks.retain(|block| !block.is_empty());
+    blocks
+}
+
+/// Reads the timing and text of a cue, which can start with a line identifying it.
+fn cue<'a>(block: &[(usize, &'a str)]) -> Result<(i64, i64, Vec<&'a str>)> {
+    let timing = block
+        .iter()
+        .take(2)
+        .position(|(_, line)| line.contains("-->"))
+        .ok_or_else(|| {
+            Error::InvalidTranscript(format!(
+                "expected a line like `00:00:01,000 --> 00:00:02,000` on line {}",
+                block[0].0
+            ))
+        })?;
+
+    let (line, timings) = block[timing];
+    let invalid = || Error::InvalidTranscript(format!("invalid timestamps on line {line}"));
+    let (start, stop) = timings.split_once("-->").ok_or_else(invalid)?;
+    // WebVTT cue settings can follow the end timestamp
+    let stop = stop.split_whitespace().next().unwrap_or_default();
+
+    Ok((
+        parse_timestamp(start.trim()).ok_or_else(invalid)?,
+        parse_timestamp(stop).ok_or_else(invalid)?,
+        block[timing + 1..].iter().map(|(_, line)| *line).collect(),
+    ))
+}
+
+/// Parses a timestamp like `01:02:03,456` or `02:03.456` into centiseconds. Minutes and seconds
+/// have to be below 60.
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    // only digits, so signs can't sneak in
+    let number = |field: &str| {
+        field
+            .chars()
+            .all(|c| c.is_ascii_digit())
+            .then(|| field.parse::<u32>().ok())
+            .flatten()
+            .map(i64::from)
+    };
+
+    let (rest, fraction) = timestamp
+        .rsplit_once(['.', ','])
+        .unwrap_or((timestamp, "0"));
+    let mut parts = rest.rsplit(':');
+    let seconds = number(parts.next()?)?;
+    let minutes = parts.next().map_or(Some(0), number)?;
+    let hours = parts.next().map_or(Some(0), number)?;
+    if parts.next().is_some() || seconds >= 60 || minutes >= 60 {
+        return None;
+    }
+
+    let milliseconds = number(&format!("{fraction:0<3}")[..3])?;
+
+    Some((((hours * 60 + minutes) * 60 + seconds) * 1000 + milliseconds) / 10)
+}
+
+/// Reads a speaker label like `Speaker 1` back into the speaker's number.
+fn parse_speaker(label: &str) -> Option<usize> {
+    label
+        .strip_prefix("Speaker ")?
+        .parse::<usize>()
+        .ok()?
+        .checked_sub(1)
+}
+
+/// Removes markup like `<i>` and `<font color="red">` from the text of an SRT cue. A `<` that
+/// doesn't start a tag, like in `x < y`, is kept, since SRT doesn't escape it.
+fn strip_tags(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        stripped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match tag_length(rest) {
+            Some(length) => rest = &rest[length..],
+            None => {
+                stripped.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    stripped.push_str(rest);
+
+    stripped.trim().to_string()
+}
+
+/// Length of the tag `text` starts with, like `<i>` or `</font>`, if it starts with one.
+fn tag_length(text: &str) -> Option<usize> {
+    let name = text.strip_prefix('<')?;
+    if !name
+        .strip_prefix('/')
+        .unwrap_or(name)
+        .starts_with(|c: char| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+
+    let end = text.find('>')?;
+    (!text[1..end].contains('<')).then_some(end + 1)
+}
+
+/// The text of a WebVTT cue, with the time each character is spoken at.
+struct Payload {
+    chars: Vec<(char, i64)>,
+    /// Where inline timestamps start new words, as indexes into `chars`.
+    breaks: Vec<usize>,
+    speaker: Option<usize>,
+    /// Whether the cue has inline timestamps.
+    timed: bool,
+    /// Whether the cue has `<c>` spans, which words are wrapped in when rendered with timestamps.
+    spans: bool,
+}
+
+impl Payload {
+    fn parse(payload: &str, start: i64) -> Self {
+        let mut parsed = Self {
+            chars: Vec::new(),
+            breaks: Vec::new(),
+            speaker: None,
+            timed: false,
+            spans: false,
+        };
+
+        let mut time = start;
+        let mut rest = payload;
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                if let Some(end) = rest.find('>') {
+                    let tag = &rest[1..end];
+                    if let Some(timestamp) = parse_timestamp(tag) {
+                        time = timestamp;
+                        parsed.timed = true;
+                        parsed.breaks.push(parsed.chars.len());
+                    } else if let Some(voice) = tag.strip_prefix('v') {
+                        // voice spans can have classes, like `<v.loud Speaker 1>`
+                        let name = voice.split_once(' ').map_or("", |(_, name)| name);
+                        parsed.speaker = parse_speaker(name.trim());
+                    } else if tag == "c" {
+                        parsed.spans = true;
+                    }
+
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+
+            if c == '&' {
+                if let Some((entity, c)) =
+                    ENTITIES.iter().find(|(entity, _)| rest.starts_with(entity))
+                {
+                    parsed.chars.push((*c, time));
+                    rest = &rest[entity.len()..];
+                    continue;
+                }
+            }
+
+            parsed.chars.push((c, time));
+            rest = &rest[c.len_utf8()..];
+        }
+
+        parsed
+    }
+
+    fn text(&self) -> String {
+        let text = self.chars.iter().map(|(c, _)| *c).collect::<String>();
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// The words of the `utterance`th cue, split by whitespace and inline timestamps. Each word
+    /// lasts until the next one starts, and the last one until the cue ends.
+    fn words(&self, stop: i64, utterance: usize) -> Vec<Utternace> {
+        let mut words: Vec<(String, i64)> = Vec::new();
+        let mut in_word = false;
+        for (i, (c, time)) in self.chars.iter().enumerate() {
+            if c.is_whitespace() {
+                in_word = false;
+            } else if in_word && !self.breaks.contains(&i) {
+                if let Some((word, _)) = words.last_mut() {
+                    word.push(*c);
+                }
+            } else {
+                in_word = true;
+                words.push((c.to_string(), *time));
+            }
+        }
+
+        let starts = words.iter().map(|(_, start)| *start).collect::<Vec<_>>();
+        words
+            .into_iter()
+            .enumerate()
+            .map(|(i, (text, start))| Utternace {
+                text,
+                start,
+                stop: starts.get(i + 1).copied().unwrap_or(stop).max(start),
+                speaker: None,
+                confidence: None,
+                tokens: Vec::new(),
+                utterance: Some(utterance),
+            })
+            .collect()
+    }
+}
+
+/// The character references that can show up in WebVTT cue text.
+const ENTITIES: &[(&str, char)] = &[
+    ("&amp;", '&'),
+    ("&lt;", '<'),
+    ("&gt;", '>'),
+    ("&quot;", '"'),
+    ("&apos;", '\''),
+    ("&nbsp;", '\u{a0}'),
+    ("&lrm;", '\u{200e}'),
+    ("&rlm;", '\u{200f}'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transcript::Token, Language};
+
+    fn utterance(text: &str, start: i64, stop: i64, speaker: Option<usize>) -> Utternace {
+        Utternace {
+            start,
+            stop,
+            text: text.to_string(),
+            tokens: Vec::new(),
+            speaker,
+            confidence: None,
+            utterance: None,
+        }
+    }
+
+    fn word(text: &str, start: i64, stop: i64, utterance: usize) -> Utternace {
+        Utternace {
+            utterance: Some(utterance),
+            ..self::utterance(text, start, stop, None)
+        }
+    }
+
+    /// What survives a round trip through subtitles: timing, text, speaker and the utterance of
+    /// each word.
+    type Cue<'a> = (i64, i64, &'a str, Option<usize>, Option<usize>);
+
+    fn cues(utterances: &[Utternace]) -> Vec<Cue<'_>> {
+        utterances
+            .iter()
+            .map(|u| (u.start, u.stop, u.text.trim(), u.speaker, u.utterance))
+            .collect()
+    }
+
+    #[test]
+    fn reads_back_srt() {
+        let transcript = Transcript::from_utterances(
+            vec![
+                utterance(" Hello there.", 0, 150, Some(0)),
+                utterance(" General Kenobi!", 200, 3_725_420, Some(1)),
+                utterance(" Nobody said this: really.", 3_725_500, 3_725_600, None),
+                utterance(" I <3 you, and x < y", 3_726_000, 3_726_100, None),
+            ],
+            None,
+        );
+
+        let parsed = Transcript::from_srt(&transcript.as_srt()).unwrap();
+        assert_eq!(cues(&parsed.utterances), cues(&transcript.utterances));
+        assert!(parsed.word_utterances.is_none());
+    }
+
+    #[test]
+    fn strips_tags_but_not_other_angle_brackets() {
+        assert_eq!(
+            strip_tags("<i>x < y</i> <font color=\"red\">and</font> <3"),
+            "x < y and <3"
+        );
+        assert_eq!(strip_tags("a <b"), "a <b");
+    }
+
+    #[test]
+    fn rejects_negative_and_out_of_range_timestamps() {
+        assert_eq!(parse_timestamp("01:02:03,456"), Some(372_345));
+        assert_eq!(parse_timestamp("02:03.456"), Some(12_345));
+
+        for timestamp in ["00:-1:00,000", "-00:01.000", "00:00:+1,000", "00:00:60,000"] {
+            assert_eq!(parse_timestamp(timestamp), None, "{timestamp}");
+        }
+        assert_eq!(parse_timestamp("00:60:00,000"), None);
+        assert_eq!(parse_timestamp("00:00:01,-10"), None);
+
+        let srt = "1\n00:-1:00,000 --> 00:00:01,000\nHello\n";
+        assert!(Transcript::from_srt(srt).is_err());
+    }
+
+    #[test]
+    fn reads_back_vtt_with_speakers_words_and_entities() {
+        let transcript = Transcript::from_utterances(
+            vec![
+                utterance(" Fish & chips <3", 100, 400, Some(0)),
+                utterance(" Goodbye.", 450, 600, Some(1)),
+                utterance(" \"Quoted\" & 'unspoken'", 700, 800, None),
+            ],
+            Some(vec![
+                word(" Fish", 100, 200, 0),
+                word(" &", 200, 250, 0),
+                word(" chips", 250, 350, 0),
+                word(" <3", 350, 400, 0),
+                word(" Goodbye.", 450, 600, 1),
+            ]),
+        );
+
+        let parsed = Transcript::from_vtt(&transcript.as_vtt()).unwrap();
+        assert_eq!(cues(&parsed.utterances), cues(&transcript.utterances));
+        assert_eq!(
+            cues(parsed.word_utterances.as_deref().unwrap()),
+            cues(transcript.word_utterances.as_deref().unwrap())
+        );
+    }
+
+    #[test]
+    fn reads_back_json() {
+        let mut transcript = Transcript::from_utterances(
+            vec![Utternace {
+                tokens: vec![Token {
+                    id: 42,
+                    text: " Hello".to_string(),
+                    p: 0.5,
+                    plog: -0.75,
+                    start: Some(0),
+                    stop: Some(100),
+                }],
+                confidence: Some(0.5),
+                ..utterance(" Hello", 0, 100, Some(2))
+            }],
+            Some(vec![word(" Hello", 0, 100, 0)]),
+        );
+        transcript.language = Some(Language::English);
+        transcript.model = Some("base.en".to_string());
+
+        let json = transcript.as_json();
+        assert_eq!(Transcript::from_json(&json).unwrap().as_json(), json);
+    }
+
+    #[test]
+    fn finds_the_utterance_of_words_saved_without_one() {
+        let transcript = Transcript::from_utterances(
+            vec![
+                utterance(" One two.", 0, 100, None),
+                utterance(" Three.", 100, 200, None),
+            ],
+            Some(vec![
+                utterance(" One", 0, 50, None),
+                utterance(" two.", 50, 100, None),
+                utterance(" Three.", 100, 200, None),
+            ]),
+        );
+
+        let parsed = Transcript::from_json(&transcript.as_json()).unwrap();
+        let utterances = parsed
+            .word_utterances
+            .unwrap()
+            .iter()
+            .map(|word| word.utterance)
+            .collect::<Vec<_>>();
+        assert_eq!(utterances, [Some(0), Some(0), Some(1)]);
+    }
+}
@@ -0,0 +1,196 @@
+use std::f64::consts::PI;
+
+/// Number of sinc zero-crossings on each side of the interpolation kernel.
+const ZERO_CROSSINGS: usize = 16;
+
+/// A streaming, band-limited (windowed sinc) sample rate converter for mono audio.
+pub struct Resampler {
+    from: u64,
+    to: u64,
+    cutoff: f64,
+    half_width: usize,
+    buffer: Vec<f32>,
+    position: u64,
+}
+
+impl Resampler {
+    /// Panics if either rate is 0, so callers have to check the rates they're given first.
+    pub fn new(from: u32, to: u32) -> Self {
+        assert!(from > 0 && to > 0, "sample rates have to be positive");
+        let cutoff = (f64::from(to) / f64::from(from)).min(1.0);
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+
+        Self {
+            cutoff,
+            half_width,
+            to: to.into(),
+            buffer: Vec::new(),
+            from: from.into(),
+            position: 0,
+        }
+    }
+
+    /// Feeds a block of samples, returning every output sample that can be computed so far.
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.from == self.to {
+            return input.to_vec();
+        }
+
+        self.buffer.extend_from_slice(input);
+        self.drain(false)
+    }
+
+    /// Flushes the remaining samples, padding the end of the signal with silence.
+    pub fn flush(&mut self) -> Vec<f32> {
+        if self.from == self.to {
+            return Vec::new();
+        }
+
+        let len = self.buffer.len();
+        self.buffer.resize(len + self.half_width, 0.0);
+        let output = self.drain(true);
+        self.buffer.clear();
+        self.position = 0;
+
+        output
+    }
+
+    fn drain(&mut self, flushing: bool) -> Vec<f32> {
+        let mut output = Vec::new();
+        let real_len = self.buffer.len() - if flushing { self.half_width } else { 0 };
+
+        loop {
+            #[allow(clippy::cast_possible_truncation)]
+            let index = (self.position / self.to) as usize;
+            if index + self.half_width >= self.buffer.len() || (flushing && index >= real_len) {
+                break;
+            }
+
+            #[allow(clippy::cast_precision_loss)]
+            let fraction = (self.position % self.to) as f64 / self.to as f64;
+            output.push(self.interpolate(index, fraction));
+            self.position += self.from;
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let consumed = ((self.position / self.to) as usize).saturating_sub(self.half_width);
+        if consumed > 0 {
+            self.buffer.drain(..consumed);
+            self.position -= consumed as u64 * self.to;
+        }
+
+        output
+    }
+
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    fn interpolate(&self, index: usize, fraction: f64) -> f32 {
+        let start = index.saturating_sub(self.half_width - 1);
+        let end = index + self.half_width;
+        let width = self.half_width as f64;
+
+        let mut sum = 0.0;
+        for (i, sample) in self.buffer[start..=end].iter().enumerate() {
+            let x = (start + i) as f64 - index as f64 - fraction;
+            if x.abs() >= width {
+                continue;
+            }
+
+            let window = 0.5 * (1.0 + (PI * x / width).cos());
+            sum += f64::from(*sample) * self.cutoff * sinc(self.cutoff * x) * window;
+        }
+
+        sum as f32
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Averages interleaved multi-channel samples into a single channel.
+pub fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Splits interleaved samples into one buffer per channel.
+pub fn deinterleave(samples: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    (0..channels)
+        .map(|channel| {
+            samples
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resample(from: u32, to: u32, input: &[f32]) -> Vec<f32> {
+        let mut resampler = Resampler::new(from, to);
+        let mut output = input
+            .chunks(1000)
+            .flat_map(|block| resampler.process(block))
+            .collect::<Vec<_>>();
+        output.extend(resampler.flush());
+        output
+    }
+
+    #[test]
+    fn produces_one_second_per_second() {
+        for (from, to) in [(48_000, 16_000), (44_100, 16_000), (8_000, 16_000)] {
+            let output = resample(from, to, &vec![0.0; from as usize]);
+            assert_eq!(output.len(), to as usize, "{from}Hz to {to}Hz");
+        }
+    }
+
+    #[test]
+    fn keeps_a_constant_signal_constant() {
+        for (from, to) in [(48_000, 16_000), (44_100, 16_000), (8_000, 16_000)] {
+            let output = resample(from, to, &vec![0.5; from as usize]);
+
+            // away from the edges, where the signal starts and stops
+            let middle = &output[output.len() / 4..output.len() * 3 / 4];
+            assert!(
+                middle.iter().all(|sample| (sample - 0.5).abs() < 1e-3),
+                "{from}Hz to {to}Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn passes_matching_rates_through() {
+        let input = [0.1, -0.2, 0.3];
+        assert_eq!(resample(16_000, 16_000, &input), input);
+    }
+
+    #[test]
+    fn downmixes_and_deinterleaves() {
+        let samples = [1.0, 3.0, -1.0, 1.0];
+
+        assert_eq!(downmix(&samples, 2), vec![2.0, 0.0]);
+        assert_eq!(downmix(&samples, 1), samples);
+        assert_eq!(
+            deinterleave(&samples, 2),
+            vec![vec![1.0, -1.0], vec![3.0, 1.0]]
+        );
+    }
+}
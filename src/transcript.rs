@@ -1,52 +1,179 @@
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
-use crate::utils::format_timestamp;
+use crate::{
+    ass::{self, AssOptions},
+    layout::{self, SubtitleLayout},
+    utils::format_timestamp,
+    words, Language, TranscribeOptions,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transcript {
     pub processing_time: Duration,
     pub utterances: Vec<Utternace>,
     pub word_utterances: Option<Vec<Utternace>>,
+    /// The language spoken in the audio, as detected by whisper.
+    #[serde(default)]
+    pub language: Option<Language>,
+    /// How likely each language was to be the one spoken in the audio. Only available when the
+    /// language was detected automatically.
+    #[serde(default)]
+    pub language_probabilities: Option<BTreeMap<Language, f32>>,
+    /// Name of the model used to generate the transcript.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub parameters: Option<Parameters>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Utternace {
     pub start: i64,
     pub stop: i64,
     pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<Token>,
+    /// Who is speaking, numbered from zero. Only set when the transcript has been diarized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<usize>,
+    /// Average probability of the tokens in the utterance. Only set for words, and for the cues
+    /// of [`Transcript::with_layout`], which no longer have tokens of their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+    /// Index of the utterance the word was spoken in. Only set for words.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utterance: Option<usize>,
+}
+
+impl Utternace {
+    /// A human-readable name for the speaker, like `Speaker 1`.
+    pub fn speaker_label(&self) -> Option<String> {
+        self.speaker
+            .map(|speaker| format!("Speaker {}", speaker + 1))
+    }
+
+    /// Moves the utterance and its tokens `ticks` later, for audio transcribed from the middle
+    /// of a recording.
+    pub(crate) fn shift(&mut self, ticks: i64) {
+        self.start += ticks;
+        self.stop += ticks;
+
+        for token in &mut self.tokens {
+            token.start = token.start.map(|tick| tick + ticks);
+            token.stop = token.stop.map(|tick| tick + ticks);
+        }
+    }
+
+    /// The trimmed text, prefixed with the speaker's name if there is one.
+    pub(crate) fn labelled_text(&self) -> String {
+        match self.speaker_label() {
+            Some(label) => format!("{label}: {}", self.text.trim()),
+            None => self.text.trim().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub id: i32,
+    pub text: String,
+    /// Probability of the token.
+    pub p: f32,
+    /// Log probability of the token.
+    pub plog: f32,
+    /// Only available when word timestamps were requested.
+    pub start: Option<i64>,
+    /// Only available when word timestamps were requested.
+    pub stop: Option<i64>,
+}
+
+/// The options a transcript was generated with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Parameters {
+    /// The language requested when transcribing, if any.
+    pub language: Option<Language>,
+    #[serde(flatten)]
+    pub options: TranscribeOptions,
 }
 
 impl Transcript {
+    /// Splits the utterances into cues that follow `layout`, for rendering as subtitles. The text
+    /// of each cue is broken into lines, and word timestamps are used to decide where to split
+    /// when available.
+    #[must_use]
+    pub fn with_layout(&self, layout: &SubtitleLayout) -> Self {
+        let (utterances, word_utterances) = layout::lay_out(self, layout);
+
+        Self {
+            utterances,
+            word_utterances,
+            ..self.clone()
+        }
+    }
+
     pub fn as_text(&self) -> String {
         self.utterances
             .iter()
             .fold(String::new(), |transcript, fragment| {
-                transcript + format!("{}\n", fragment.text.trim()).as_str()
+                transcript + format!("{}\n", fragment.labelled_text()).as_str()
             })
     }
 
+    /// Renders one cue per utterance. With word timestamps, each word gets an inline timestamp
+    /// tag and a `<c>` class, so players highlight the words as they are spoken (and they can be
+    /// styled with the `::cue(:past)` and `::cue(:future)` selectors).
     pub fn as_vtt(&self) -> String {
-        self.word_utterances
-            .as_ref()
-            .unwrap_or(&self.utterances)
-            .iter()
-            .fold(String::new(), |transcript, fragment| {
+        let mut words = self.words_by_utterance();
+
+        self.utterances.iter().enumerate().fold(
+            String::from("WEBVTT\n"),
+            |transcript, (i, fragment)| {
+                let text = match words.as_mut().and_then(|words| take_words(words, i)) {
+                    Some(words) => self
+                        .karaoke_lines(fragment, words)
+                        .iter()
+                        .map(|line| {
+                            line.iter()
+                                .map(|word| {
+                                    let text = format!("<c>{}</c>", escape_vtt(word.text.trim()));
+                                    // timestamp tags have to be strictly inside the cue
+                                    if word.start > fragment.start && word.start < fragment.stop {
+                                        format!(
+                                            "<{}>{text}",
+                                            format_timestamp(word.start, false, ".")
+                                        )
+                                    } else {
+                                        text
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                                .join(self.word_separator())
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    None => escape_vtt(fragment.text.trim()),
+                };
+
                 transcript
                     + format!(
-                        "{} --> {}\n{}\n",
+                        "\n{} --> {}\n{}\n",
                         format_timestamp(fragment.start, false, "."),
                         format_timestamp(fragment.stop, false, "."),
-                        fragment.text.trim().replace("-->", "->")
+                        match fragment.speaker_label() {
+                            Some(label) => format!("<v {label}>{text}"),
+                            None => text,
+                        }
                     )
                     .as_str()
-            })
+            },
+        )
     }
 
+    /// Renders one cue per utterance. SRT has no way to highlight words, so word timestamps
+    /// aren't used.
     pub fn as_srt(&self) -> String {
-        self.word_utterances
-            .as_ref()
-            .unwrap_or(&self.utterances)
+        self.utterances
             .iter()
             .fold((1, String::new()), |(i, transcript), fragment| {
                 (
@@ -56,11 +183,189 @@ impl Transcript {
                             "\n{i}\n{} --> {}\n{}\n",
                             format_timestamp(fragment.start, true, ","),
                             format_timestamp(fragment.stop, true, ","),
-                            fragment.text.trim().replace("-->", "->")
+                            fragment.labelled_text().replace("-->", "->")
                         )
                         .as_str(),
                 )
             })
             .1
     }
+
+    /// Renders the transcript as Advanced SubStation Alpha subtitles, with the default style.
+    pub fn as_ass(&self) -> String {
+        self.as_ass_with(&AssOptions::default())
+    }
+
+    /// Renders the transcript as Advanced SubStation Alpha subtitles, one line per utterance.
+    /// With word timestamps, each word gets a `\k` karaoke tag, so it's highlighted as it's
+    /// spoken.
+    pub fn as_ass_with(&self, options: &AssOptions) -> String {
+        let speakers = self
+            .utterances
+            .iter()
+            .filter_map(|utterance| utterance.speaker)
+            .max()
+            .map_or(0, |speaker| speaker + 1);
+        let mut ass = options.header(speakers);
+        let mut words_by_utterance = self.words_by_utterance();
+
+        for (i, fragment) in self.utterances.iter().enumerate() {
+            let words = words_by_utterance
+                .as_mut()
+                .and_then(|words| take_words(words, i));
+            let confidence = words.as_ref().map_or_else(
+                || fragment.confidence.or_else(|| average_p(&fragment.tokens)),
+                |words| average(words.iter().filter_map(|word| word.confidence)),
+            );
+
+            let text = match words {
+                Some(words) => {
+                    let mut text = String::new();
+                    let mut cursor = fragment.start;
+
+                    for (i, line) in self.karaoke_lines(fragment, words).iter().enumerate() {
+                        if i > 0 {
+                            text += "\\N";
+                        }
+
+                        for (j, word) in line.iter().enumerate() {
+                            if j > 0 {
+                                text += self.word_separator();
+                            }
+
+                            // `\k` durations follow on from each other, so pauses need their own
+                            if word.start > cursor {
+                                text += &format!("{{\\k{}}}", word.start - cursor);
+                            }
+
+                            let start = word.start.max(cursor);
+                            let stop = word.stop.max(start);
+                            text += &format!(
+                                "{{\\k{}}}{}",
+                                stop - start,
+                                ass::escape(word.text.trim())
+                            );
+                            cursor = stop;
+                        }
+                    }
+
+                    text
+                }
+                None => ass::escape(fragment.text.trim()),
+            };
+
+            ass += &format!(
+                "Dialogue: 0,{},{},{},{},0,0,0,,{text}\n",
+                ass::timestamp(fragment.start),
+                ass::timestamp(fragment.stop),
+                options.style_for(fragment.speaker, confidence),
+                fragment.speaker_label().unwrap_or_default(),
+            );
+        }
+
+        ass
+    }
+
+    pub fn as_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("transcripts should always serialize")
+    }
+
+    /// Renders one JSON object per utterance, one per line.
+    pub fn as_json_lines(&self) -> String {
+        self.utterances
+            .iter()
+            .fold(String::new(), |transcript, fragment| {
+                transcript
+                    + serde_json::to_string(fragment)
+                        .expect("utterances should always serialize")
+                        .as_str()
+                    + "\n"
+            })
+    }
+
+    /// The words of each utterance, if the transcript has word timestamps.
+    pub(crate) fn words_by_utterance(&self) -> Option<Vec<Vec<&Utternace>>> {
+        let words = self.word_utterances.as_ref()?;
+
+        let mut by_utterance = vec![Vec::new(); self.utterances.len()];
+        for word in words {
+            if let Some(utterance) = word.utterance.and_then(|i| by_utterance.get_mut(i)) {
+                utterance.push(word);
+            }
+        }
+
+        Some(by_utterance)
+    }
+
+    /// Splits the words of `utterance` into the lines its text is broken into, like it is by
+    /// [`Transcript::with_layout`].
+    fn karaoke_lines<'a>(
+        &self,
+        utterance: &Utternace,
+        words: Vec<&'a Utternace>,
+    ) -> Vec<Vec<&'a Utternace>> {
+        let length = |text: &str| text.chars().filter(|c| !c.is_whitespace()).count();
+
+        let mut words = words.into_iter();
+        let mut lines = utterance
+            .text
+            .trim()
+            .lines()
+            .map(|line| {
+                let mut taken = Vec::new();
+                let mut remaining = length(line);
+                while remaining > 0 {
+                    let Some(word) = words.next() else { break };
+                    remaining = remaining.saturating_sub(length(&word.text));
+                    taken.push(word);
+                }
+
+                taken
+            })
+            .collect::<Vec<_>>();
+
+        // words that don't match up with the text go on the last line
+        if let Some(last) = lines.last_mut() {
+            last.extend(words);
+        }
+        lines.retain(|line| !line.is_empty());
+
+        lines
+    }
+
+    /// What goes between words on the same line.
+    pub(crate) fn word_separator(&self) -> &'static str {
+        if words::is_unspaced(self.language) {
+            ""
+        } else {
+            " "
+        }
+    }
+}
+
+/// Takes the words of the `i`th utterance out of `words`, if it has any.
+fn take_words<'a>(words: &mut [Vec<&'a Utternace>], i: usize) -> Option<Vec<&'a Utternace>> {
+    let words = std::mem::take(words.get_mut(i)?);
+    (!words.is_empty()).then_some(words)
+}
+
+/// Escapes the characters that have a meaning in WebVTT cue text. Escaping `>` also keeps `-->`
+/// out of the cue.
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub(crate) fn average_p(tokens: &[Token]) -> Option<f32> {
+    average(tokens.iter().map(|token| token.p))
+}
+
+fn average(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0.0, 0_usize), |(sum, count), value| {
+        (sum + value, count + 1)
+    });
+
+    #[allow(clippy::cast_precision_loss)]
+    (count > 0).then(|| sum / count as f32)
 }
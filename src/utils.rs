@@ -1,63 +1,179 @@
-#![allow(dead_code)]
+use crate::{
+    checksum::is_sha256,
+    error::{Error, Result},
+};
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
-use num::integer::div_floor;
-use reqwest::Client;
-use std::{cmp::min, fs::File, io::Write, path::PathBuf};
+use reqwest::{header::RANGE, redirect::Policy, Client, StatusCode};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Downloads `url` to `path`, returning the SHA-256 checksum of the file.
+///
+/// The file is written to `path` with a `.partial` suffix first, and only renamed to `path` once
+/// it's complete and matches `expected` (if provided). If a partial file is already there from an
+/// interrupted download, the download picks up where it left off.
+#[allow(clippy::literal_string_with_formatting_args)]
+pub async fn download_file(url: &str, path: &Path, expected: Option<&str>) -> Result<String> {
+    let download_error = |source| Error::Download {
+        source,
+        url: url.to_string(),
+    };
 
-pub async fn download_file(url: &str, path: &str) {
-    let res = Client::new()
-        .get(url)
-        .send()
-        .await
-        .or(Err(format!("Failed to GET from '{}'", &url)))
-        .unwrap();
+    let partial = with_suffix(path, ".partial");
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&partial)?;
+
+    // hash what's already there, so the checksum covers the whole file
+    let mut hasher = Sha256::new();
+    let mut downloaded = io::copy(&mut file, &mut hasher)?;
+
+    let mut req = Client::new().get(url);
+    if downloaded > 0 {
+        req = req.header(RANGE, format!("bytes={downloaded}-"));
+    }
+
+    let res = req.send().await.map_err(download_error)?;
+    let resumed = downloaded > 0 && res.status() == StatusCode::PARTIAL_CONTENT;
+    if res.status() == StatusCode::RANGE_NOT_SATISFIABLE && downloaded > 0 {
+        // the partial file is already complete
+        return finish_download(partial, path, hasher, expected);
+    }
 
-    let total_size = res
-        .content_length()
-        .ok_or(format!("Failed to get content length from '{}'", &url))
-        .unwrap();
+    let res = res.error_for_status().map_err(download_error)?;
+    if !resumed {
+        // the server sent the whole file, so start over
+        file.set_len(0)?;
+        hasher = Sha256::new();
+        downloaded = 0;
+    }
+
+    let total_size = res.content_length().unwrap_or_default() + downloaded;
 
     let pb = ProgressBar::new(total_size);
     pb.set_style(ProgressStyle::default_bar()
-        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})").unwrap()
+        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})").expect("progress bar template should be valid")
         .progress_chars("#>-"));
-    pb.set_message(format!("Downloading {url}",));
-
-    let mut file = File::create(path)
-        .or(Err(format!("Failed to create file '{path}'")))
-        .unwrap();
+    pb.set_position(downloaded);
+    pb.set_message(if resumed {
+        format!("Resuming download of {url}")
+    } else {
+        format!("Downloading {url}")
+    });
 
-    let mut downloaded: u64 = 0;
     let mut stream = res.bytes_stream();
 
     while let Some(item) = stream.next().await {
-        let chunk = item.or(Err("Error while downloading file")).unwrap();
+        let chunk = item.map_err(download_error)?;
+        file.write_all(&chunk)?;
+        hasher.update(&chunk);
 
-        file.write_all(&chunk)
-            .or(Err("Error while writing to file"))
-            .unwrap();
+        downloaded += chunk.len() as u64;
+        pb.set_position(downloaded);
+    }
+
+    file.sync_all()?;
+    drop(file);
 
-        let new = min(downloaded + (chunk.len() as u64), total_size);
-        pb.set_position(new);
-        downloaded = new;
+    let hash = finish_download(partial, path, hasher, expected)?;
+    pb.finish_with_message(format!("Downloaded {url} to {}", path.display()));
+
+    Ok(hash)
+}
+
+/// Checks the downloaded file against the expected checksum, and moves it into place.
+fn finish_download(
+    partial: PathBuf,
+    path: &Path,
+    hasher: Sha256,
+    expected: Option<&str>,
+) -> Result<String> {
+    let actual = hex::encode(hasher.finalize());
+
+    if let Some(expected) = expected.filter(|expected| !expected.eq_ignore_ascii_case(&actual)) {
+        // the partial file can't be resumed from either, since we don't know where it went wrong
+        fs::remove_file(&partial)?;
+
+        return Err(Error::ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: expected.to_string(),
+            actual,
+        });
     }
 
-    pb.finish_with_message(format!("Downloaded {url} to {path}"));
+    fs::rename(partial, path)?;
+
+    Ok(actual)
 }
 
-pub fn format_timestamp(seconds: i64, always_include_hours: bool, decimal_marker: &str) -> String {
-    assert!(seconds >= 0, "non-negative timestamp expected");
-    let mut milliseconds = seconds * 10;
+/// Takes an exclusive lock on `path` (creating it if needed), waiting for whoever is holding it to
+/// let go (after calling `on_wait`, if there's anyone). The lock is released when the returned
+/// file is dropped.
+pub async fn lock_file(path: &Path, on_wait: Option<fn(&Path)>) -> Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    match file.try_lock() {
+        Ok(()) => return Ok(file),
+        Err(TryLockError::Error(e)) => return Err(e.into()),
+        Err(TryLockError::WouldBlock) => {}
+    }
+
+    if let Some(on_wait) = on_wait {
+        on_wait(path);
+    }
+    let file = tokio::task::spawn_blocking(move || file.lock().map(|()| file))
+        .await
+        .map_err(io::Error::other)??;
+
+    Ok(file)
+}
 
-    let hours = div_floor(milliseconds, 3_600_000);
-    milliseconds -= hours * 3_600_000;
+/// Appends `suffix` to the file name in `path`.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
 
-    let minutes = div_floor(milliseconds, 60_000);
-    milliseconds -= minutes * 60_000;
+    path.into()
+}
+
+/// Asks the server for the SHA-256 checksum of a file, without downloading it.
+///
+/// Hugging Face serves large files from a CDN, and reports their checksum in the `X-Linked-Etag`
+/// header of the redirect to it.
+pub async fn fetch_checksum(url: &str) -> Option<String> {
+    let res = Client::builder()
+        .redirect(Policy::none())
+        .build()
+        .ok()?
+        .head(url)
+        .send()
+        .await
+        .ok()?;
+
+    ["x-linked-etag", "etag"].iter().find_map(|header| {
+        let etag = res.headers().get(*header)?.to_str().ok()?;
+        let etag = etag.trim_start_matches("W/").trim_matches('"');
+
+        is_sha256(etag).then(|| etag.to_lowercase())
+    })
+}
 
-    let seconds = div_floor(milliseconds, 1_000);
-    milliseconds -= seconds * 1_000;
+/// Formats a timestamp (in centiseconds) like `01:02.500`, or `1:01:02.500` past an hour or
+/// with `always_include_hours`. Negative timestamps are formatted as 0.
+pub fn format_timestamp(ticks: i64, always_include_hours: bool, decimal_marker: &str) -> String {
+    let (hours, minutes, seconds, centiseconds) = split_timestamp(ticks);
+    let milliseconds = centiseconds * 10;
 
     let hours_marker = if always_include_hours || hours != 0 {
         format!("{hours}:")
@@ -68,9 +184,14 @@ pub fn format_timestamp(seconds: i64, always_include_hours: bool, decimal_marker
     format!("{hours_marker}{minutes:02}:{seconds:02}{decimal_marker}{milliseconds:03}")
 }
 
-pub fn write_to(path: PathBuf, content: &String) {
-    File::create(path)
-        .unwrap()
-        .write_all(content.as_bytes())
-        .unwrap();
+/// Splits a timestamp (in centiseconds) into hours, minutes, seconds and centiseconds, for each
+/// format to lay out its own way. Negative timestamps count as 0.
+pub(crate) fn split_timestamp(ticks: i64) -> (i64, i64, i64, i64) {
+    let ticks = ticks.max(0);
+    (
+        ticks / 360_000,
+        ticks / 6_000 % 60,
+        ticks / 100 % 60,
+        ticks % 100,
+    )
 }
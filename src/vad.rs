@@ -0,0 +1,162 @@
+use crate::chunked::duration_to_samples;
+use std::{ops::Range, time::Duration};
+
+/// Finds the parts of a recording that contain speech, so silence can be skipped before inference.
+pub trait VoiceActivityDetector {
+    /// Returns the sorted, non-overlapping ranges of `samples` (16kHz mono) that contain speech.
+    fn detect(&self, samples: &[f32]) -> Vec<Range<usize>>;
+}
+
+/// A voice activity detector that considers any stretch of audio noticeably louder than the
+/// background noise to be speech.
+#[derive(Debug, Clone, Copy)]
+pub struct EnergyVad {
+    /// How much louder than the noise floor (in dB) a frame has to be to count as speech.
+    pub threshold: f32,
+    /// Speech shorter than this is dropped as noise.
+    pub min_speech: Duration,
+    /// Pauses shorter than this are kept, instead of splitting the speech around them.
+    pub min_silence: Duration,
+    /// Audio kept on each side of a speech region, so the start and end of words aren't cut off.
+    pub padding: Duration,
+}
+
+impl Default for EnergyVad {
+    fn default() -> Self {
+        Self {
+            threshold: 10.0,
+            min_speech: Duration::from_millis(250),
+            min_silence: Duration::from_secs(1),
+            padding: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Length of the frames the energy is measured over (30ms at 16kHz).
+const FRAME_LENGTH: usize = 480;
+/// Frames quieter than this (in dBFS) are never speech, however quiet the recording is.
+const SILENCE_FLOOR: f32 = -50.0;
+
+impl VoiceActivityDetector for EnergyVad {
+    fn detect(&self, samples: &[f32]) -> Vec<Range<usize>> {
+        #[allow(clippy::cast_precision_loss)]
+        let levels = samples
+            .chunks(FRAME_LENGTH)
+            .map(|frame| {
+                let power = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+                10.0 * power.max(1e-10).log10()
+            })
+            .collect::<Vec<_>>();
+
+        // the quietest tenth of the recording is assumed to be background noise
+        let mut sorted = levels.clone();
+        sorted.sort_by(f32::total_cmp);
+        let noise_floor = sorted
+            .get(sorted.len() / 10)
+            .copied()
+            .unwrap_or(SILENCE_FLOOR);
+        // and the loudest tenth to be speech. Recordings without pauses have no background noise
+        // to measure, so the threshold can't go so far above the quiet parts of speech that it
+        // passes the loud ones too.
+        let speech_level = sorted
+            .get(sorted.len() * 9 / 10)
+            .copied()
+            .unwrap_or(SILENCE_FLOOR);
+        let threshold = (noise_floor + self.threshold)
+            .min(speech_level - self.threshold)
+            .max(SILENCE_FLOOR);
+
+        let mut regions: Vec<Range<usize>> = Vec::new();
+        for (i, level) in levels.iter().enumerate() {
+            if *level < threshold {
+                continue;
+            }
+
+            let frame = i * FRAME_LENGTH..((i + 1) * FRAME_LENGTH).min(samples.len());
+            match regions.last_mut() {
+                Some(last) if frame.start - last.end < duration_to_samples(self.min_silence) => {
+                    last.end = frame.end;
+                }
+                _ => regions.push(frame),
+            }
+        }
+
+        let padding = duration_to_samples(self.padding);
+        let mut padded: Vec<Range<usize>> = Vec::new();
+        for region in regions {
+            if region.len() < duration_to_samples(self.min_speech) {
+                continue;
+            }
+
+            let region =
+                region.start.saturating_sub(padding)..(region.end + padding).min(samples.len());
+            match padded.last_mut() {
+                Some(last) if region.start <= last.end => last.end = region.end,
+                _ => padded.push(region),
+            }
+        }
+
+        padded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `frames` frames of a 440Hz tone at half of full scale.
+    fn tone(frames: usize) -> Vec<f32> {
+        #[allow(clippy::cast_precision_loss)]
+        (0..frames * FRAME_LENGTH)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16_000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn finds_a_tone_between_silence() {
+        let samples = [
+            vec![0.0; 40 * FRAME_LENGTH],
+            tone(40),
+            vec![0.0; 40 * FRAME_LENGTH],
+        ]
+        .concat();
+        let vad = EnergyVad::default();
+        let padding = duration_to_samples(vad.padding);
+
+        assert_eq!(
+            vad.detect(&samples),
+            vec![40 * FRAME_LENGTH - padding..80 * FRAME_LENGTH + padding]
+        );
+    }
+
+    #[test]
+    fn keeps_short_pauses_and_drops_short_noises() {
+        let samples = [
+            vec![0.0; 40 * FRAME_LENGTH],
+            tone(20),
+            // 300ms
+            vec![0.0; 10 * FRAME_LENGTH],
+            tone(20),
+            vec![0.0; 100 * FRAME_LENGTH],
+            // 60ms
+            tone(2),
+            vec![0.0; 40 * FRAME_LENGTH],
+        ]
+        .concat();
+        let vad = EnergyVad::default();
+        let padding = duration_to_samples(vad.padding);
+
+        assert_eq!(
+            vad.detect(&samples),
+            vec![40 * FRAME_LENGTH - padding..90 * FRAME_LENGTH + padding]
+        );
+    }
+
+    #[test]
+    fn finds_nothing_in_silence() {
+        assert!(EnergyVad::default()
+            .detect(&vec![0.0; 100 * FRAME_LENGTH])
+            .is_empty());
+        assert!(EnergyVad::default().detect(&[]).is_empty());
+    }
+}
@@ -1,13 +1,33 @@
 use crate::{
-    ffmpeg_decoder,
-    model::Model,
-    transcript::{Transcript, Utternace},
+    callbacks::{Callbacks, Hooks},
+    chunked::{duration_to_samples, Chunking, Chunks, SAMPLES_PER_TICK},
+    decoder,
+    error::{Error, Result},
+    live::{Live, LiveOptions},
+    model::{Model, Size},
+    options::TranscribeOptions,
+    transcript::{Parameters, Token, Transcript, Utternace},
+    vad::VoiceActivityDetector,
+    words::group_words,
 };
-use anyhow::{anyhow, Result};
-use std::{path::Path, time::Instant};
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext};
+use clap::ValueEnum;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ffi::{c_int, c_void},
+    num::NonZeroUsize,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+use whisper_rs::{
+    DtwMode, DtwModelPreset, DtwParameters, FullParams, SamplingStrategy, WhisperContext,
+    WhisperContextParameters, WhisperError, WhisperState,
+};
+use whisper_rs_sys::{whisper_context, whisper_state, whisper_token_data};
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
 pub enum Language {
     #[clap(name = "auto")]
     Auto,
@@ -211,6 +231,37 @@ pub enum Language {
     Sundanese,
 }
 
+impl Language {
+    /// Looks up a language by its ISO 639-1 code (as reported by whisper).
+    pub fn from_code(code: &str) -> Option<Self> {
+        <Self as ValueEnum>::from_str(code, true).ok()
+    }
+}
+
+/// The result of [`Whisper::detect_language`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageDetection {
+    /// The most likely language.
+    pub language: Language,
+    /// How likely each language is.
+    pub probabilities: BTreeMap<Language, f32>,
+}
+
+impl Serialize for Language {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str((*self).into())
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+
+        Self::from_code(&code)
+            .ok_or_else(|| de::Error::custom(format!("unknown language code {code}")))
+    }
+}
+
 impl From<Language> for &str {
     #[allow(clippy::too_many_lines)]
     fn from(val: Language) -> Self {
@@ -322,93 +373,614 @@ impl From<Language> for &str {
 pub struct Whisper {
     ctx: WhisperContext,
     lang: Option<Language>,
+    model: String,
+    vad: Option<Box<dyn VoiceActivityDetector + Send + Sync>>,
+    /// Whether word timestamps come from aligning the audio with DTW.
+    dtw: bool,
 }
 
 impl Whisper {
-    pub async fn new(model: Model, lang: Option<Language>) -> Self {
-        model.download().await;
+    pub async fn new(model: Model, lang: Option<Language>) -> Result<Self> {
+        Self::load(model, lang, WhisperContextParameters::default()).await
+    }
+
+    /// Like [`Whisper::new`], but timing words by aligning them with the audio using dynamic time
+    /// warping (DTW) on the model's attention heads, which is slower but more accurate than
+    /// whisper's token timestamps. Only the standard models have known alignment heads, so
+    /// distilled and custom models fail with [`Error::NoAlignmentHeads`].
+    pub async fn new_with_dtw(model: Model, lang: Option<Language>) -> Result<Self> {
+        let preset = model
+            .variant()
+            .and_then(|variant| alignment_heads(variant.size))
+            .ok_or_else(|| Error::NoAlignmentHeads(model.name()))?;
+
+        let mut params = WhisperContextParameters::default();
+        params.dtw_parameters(DtwParameters {
+            mode: DtwMode::ModelPreset {
+                model_preset: preset,
+            },
+            ..DtwParameters::default()
+        });
+
+        let mut whisper = Self::load(model, lang, params).await?;
+        whisper.dtw = true;
+        Ok(whisper)
+    }
+
+    async fn load(
+        model: Model,
+        lang: Option<Language>,
+        params: WhisperContextParameters<'_>,
+    ) -> Result<Self> {
+        model.download().await?;
 
-        Self {
+        let path = model.get_path()?;
+        let ctx =
+            WhisperContext::new_with_params(&path.to_string_lossy(), params).map_err(|source| {
+                Error::ModelLoad {
+                    path: path.clone(),
+                    source,
+                }
+            })?;
+
+        // there is nothing to detect with English-only models
+        let lang = match lang {
+            None | Some(Language::Auto) if !ctx.is_multilingual() => Some(Language::English),
+            lang => lang,
+        };
+
+        Ok(Self {
+            ctx,
             lang,
-            ctx: WhisperContext::new(model.get_path().to_str().unwrap())
-                .expect("Failed to load model."),
-        }
+            vad: None,
+            dtw: false,
+            model: model.name(),
+        })
+    }
+
+    /// Whether the model can transcribe languages other than English.
+    pub fn is_multilingual(&self) -> bool {
+        self.ctx.is_multilingual()
+    }
+
+    /// Skips the audio `vad` doesn't consider speech when calling [`Whisper::transcribe`], which
+    /// speeds it up and keeps whisper from hallucinating text during long silences. Each stretch
+    /// of speech is transcribed on its own, and timestamps still refer to the original recording.
+    #[must_use]
+    pub fn with_vad(mut self, vad: impl VoiceActivityDetector + Send + Sync + 'static) -> Self {
+        self.vad = Some(Box::new(vad));
+        self
     }
 
     pub fn transcribe<P: AsRef<Path>>(
-        &mut self,
+        &self,
         audio: P,
-        translate: bool,
-        word_timestamps: bool,
+        options: &TranscribeOptions,
     ) -> Result<Transcript> {
-        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        self.transcribe_with(audio, None, options, Callbacks::default())
+    }
 
-        params.set_translate(translate);
-        params.set_print_special(false);
-        params.set_print_progress(false);
-        params.set_print_realtime(false);
-        params.set_print_timestamps(false);
-        params.set_token_timestamps(word_timestamps);
-        params.set_language(self.lang.map(Into::into));
+    /// Like [`Whisper::transcribe`], but for audio spoken in `lang` instead of the language the
+    /// model was loaded with.
+    pub fn transcribe_in<P: AsRef<Path>>(
+        &self,
+        audio: P,
+        lang: Language,
+        options: &TranscribeOptions,
+    ) -> Result<Transcript> {
+        self.transcribe_with(audio, Some(lang), options, Callbacks::default())
+    }
+
+    /// Like [`Whisper::transcribe`], but reporting progress and new segments to `callbacks` as
+    /// the transcription runs, and stopping early with [`Error::Cancelled`] if they ask it to.
+    /// Audio is assumed to be spoken in `lang`, or the language the model was loaded with if
+    /// `None`.
+    pub fn transcribe_with<P: AsRef<Path>>(
+        &self,
+        audio: P,
+        lang: Option<Language>,
+        options: &TranscribeOptions,
+        callbacks: Callbacks<'_>,
+    ) -> Result<Transcript> {
+        let audio = decoder::read_file(audio)?;
+        self.transcribe_samples(&audio, lang, options, callbacks)
+    }
 
-        let audio = ffmpeg_decoder::read_file(audio)?;
+    /// Like [`Whisper::transcribe_with`], for audio that has already been decoded (as 16kHz mono
+    /// samples, like [`crate::decode_file`] returns).
+    pub fn transcribe_samples(
+        &self,
+        audio: &[f32],
+        lang: Option<Language>,
+        options: &TranscribeOptions,
+        mut callbacks: Callbacks<'_>,
+    ) -> Result<Transcript> {
+        let lang = lang.or(self.lang);
+        let word_timestamps = options.word_timestamps;
 
         let st = Instant::now();
-        let mut state = self.ctx.create_state().expect("failed to create state");
-        state.full(params, &audio).expect("failed to transcribe");
+        let regions = match &self.vad {
+            Some(vad) => vad.detect(audio),
+            None => std::iter::once(0..audio.len()).collect(),
+        };
+        let total = regions.iter().map(ExactSizeIterator::len).sum::<usize>();
+        if total == 0 {
+            return Err(Error::NoSpeech);
+        }
 
-        let num_segments = state.full_n_segments().expect("failed to get segments");
-        if num_segments == 0 {
-            return Err(anyhow!("No segments found"));
+        // detect the language up front (instead of letting whisper do it) to record how likely
+        // each language was
+        let detection = if matches!(lang, None | Some(Language::Auto)) {
+            let speech = regions
+                .iter()
+                .flat_map(|region| &audio[region.clone()])
+                .copied()
+                .take(duration_to_samples(DETECTION_WINDOW))
+                .collect::<Vec<_>>();
+
+            Some(self.detect(&speech)?)
+        } else {
+            None
         };
 
-        let mut words = Vec::new();
+        let requested = lang;
+        let lang = detection
+            .as_ref()
+            .map_or(lang, |detection| Some(detection.language));
+        let decoding = Decoding::new(&self.ctx, lang, options)?;
+
+        if callbacks.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let mut hooks = Hooks {
+            callbacks: &mut callbacks,
+            offset: 0,
+            progress: (0.0, 1.0),
+        };
+        let mut state = self.ctx.create_state()?;
         let mut utterances = Vec::new();
-        for s in 0..num_segments {
-            let text = state
-                .full_get_segment_text(s)
-                .map_err(|e| anyhow!("failed to get segment due to {:?}", e))?;
-            let start = state
-                .full_get_segment_t0(s)
-                .map_err(|e| anyhow!("failed to get segment due to {:?}", e))?;
-            let stop = state
-                .full_get_segment_t1(s)
-                .map_err(|e| anyhow!("failed to get segment due to {:?}", e))?;
-
-            utterances.push(Utternace { text, start, stop });
-
-            if !word_timestamps {
-                continue;
-            }
+        let mut token_bytes = Vec::new();
+        let mut language = None;
+        let mut done = 0;
 
-            let num_tokens = state
-                .full_n_tokens(s)
-                .map_err(|e| anyhow!("failed to get segment due to {:?}", e))?;
+        // each stretch of speech is transcribed on its own, so segments never run across the
+        // silence that was skipped between them
+        for region in &regions {
+            let offset = i64::try_from(region.start / SAMPLES_PER_TICK).unwrap_or(i64::MAX);
+            #[allow(clippy::cast_precision_loss)]
+            let progress = (
+                done as f32 / total as f32,
+                region.len() as f32 / total as f32,
+            );
+            hooks.offset = offset;
+            hooks.progress = progress;
 
-            for t in 0..num_tokens {
-                let text = state
-                    .full_get_token_text(s, t)
-                    .map_err(|e| anyhow!("failed to get token due to {:?}", e))?;
-                let token_data = state
-                    .full_get_token_data(s, t)
-                    .map_err(|e| anyhow!("failed to get token due to {:?}", e))?;
+            let mut params = decoding.params();
+            // SAFETY: `hooks` outlives the inference run below, which is the only place the
+            // callbacks are called from.
+            unsafe { hooks.install(&mut params) };
 
-                if text.starts_with("[_") {
-                    continue;
-                }
+            // whisper skips audio shorter than a second, so pad it with silence
+            let mut samples = Cow::Borrowed(&audio[region.clone()]);
+            if samples.len() < MIN_INFERENCE_SAMPLES {
+                samples.to_mut().resize(MIN_INFERENCE_SAMPLES, 0.0);
+            }
+            state.full(params, &samples)?;
+
+            // whisper stops quietly when the encoder callback asks it to
+            if hooks.callbacks.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
 
-                words.push(Utternace {
-                    text,
-                    start: token_data.t0,
-                    stop: token_data.t1,
-                });
+            for mut utterance in segments(&state, word_timestamps, self.dtw)? {
+                utterance.shift(offset);
+                utterances.push(utterance);
             }
+            token_bytes.extend(segment_token_bytes(&state)?);
+
+            language = language.or_else(|| {
+                state
+                    .full_lang_id_from_state()
+                    .ok()
+                    .and_then(whisper_rs::get_lang_str)
+                    .and_then(Language::from_code)
+            });
+            done += region.len();
         }
 
+        callbacks.report_progress(1.0);
+
+        if utterances.is_empty() {
+            return Err(Error::NoSpeech);
+        };
+
+        let lang = lang.filter(|lang| *lang != Language::Auto).or(language);
+
+        let words = word_timestamps.then(|| group_words(&utterances, &token_bytes, lang));
+
         Ok(Transcript {
+            language: lang,
+            language_probabilities: detection.map(|detection| detection.probabilities),
             utterances,
+            word_utterances: words,
+            model: Some(self.model.clone()),
             processing_time: Instant::now().duration_since(st),
-            word_utterances: if word_timestamps { Some(words) } else { None },
+            parameters: Some(Parameters {
+                language: requested,
+                options: options.clone(),
+            }),
+        })
+    }
+
+    /// Works out which language is spoken in the first `duration` of an audio file (whisper only
+    /// looks at the first 30 seconds).
+    pub fn detect_language<P: AsRef<Path>>(
+        &self,
+        audio: P,
+        duration: Duration,
+    ) -> Result<LanguageDetection> {
+        // only decode as much as gets listened to
+        let wanted = duration_to_samples(duration);
+        let mut samples = Vec::new();
+        for chunk in decoder::stream_file(audio)? {
+            samples.extend(chunk?);
+            if samples.len() >= wanted {
+                break;
+            }
+        }
+        samples.truncate(wanted);
+
+        self.detect(&samples)
+    }
+
+    fn detect(&self, samples: &[f32]) -> Result<LanguageDetection> {
+        let mut detection = Detection {
+            result: 0,
+            probabilities: vec![
+                0.0;
+                usize::try_from(whisper_rs::get_lang_max_id()).unwrap_or(0) + 1
+            ],
+        };
+
+        // whisper skips audio shorter than a second, so pad it with silence
+        let mut samples = samples.to_vec();
+        samples.resize(samples.len().max(MIN_INFERENCE_SAMPLES), 0.0);
+
+        // whisper-rs' own `lang_detect` misreads whisper.cpp's return value, so run the detection
+        // from the encoder callback instead, which gets a handle to the state, and stop there.
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(Language::English.into()));
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        // SAFETY: `detection` outlives the inference run below, which is the only place the
+        // callback is called from.
+        unsafe {
+            params.set_start_encoder_callback(Some(detect_language));
+            params.set_start_encoder_callback_user_data(
+                std::ptr::addr_of_mut!(detection).cast::<c_void>(),
+            );
+        }
+
+        let mut state = self.ctx.create_state()?;
+        state.full(params, &samples)?;
+
+        if detection.result < 0 {
+            return Err(Error::Inference(WhisperError::GenericError(
+                detection.result,
+            )));
+        }
+
+        let probabilities = detection
+            .probabilities
+            .iter()
+            .zip(0..)
+            .filter_map(|(probability, id)| {
+                let language = whisper_rs::get_lang_str(id).and_then(Language::from_code)?;
+                Some((language, *probability))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let language = probabilities
+            .iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(language, _)| *language)
+            .ok_or(Error::NoSpeech)?;
+
+        Ok(LanguageDetection {
+            language,
+            probabilities,
         })
     }
+
+    /// Transcribes an audio file one window at a time, yielding utterances as soon as they're ready.
+    ///
+    /// Unlike [`Whisper::transcribe`], the file is decoded lazily, so memory usage stays bounded
+    /// by the window size regardless of how long the recording is.
+    ///
+    /// Word timestamps aren't supported, so `options.word_timestamps` is ignored. Fails with
+    /// [`Error::InvalidOptions`] if `chunking` can't be used, like an overlap as long as a window.
+    pub fn transcribe_chunked<P: AsRef<Path>>(
+        &self,
+        audio: P,
+        options: &TranscribeOptions,
+        chunking: Chunking,
+    ) -> Result<Chunks> {
+        chunking.validate()?;
+
+        let options = TranscribeOptions {
+            word_timestamps: false,
+            ..options.clone()
+        };
+        let decoding = Decoding::new(&self.ctx, self.lang, &options)?;
+        let source = decoder::stream_file(audio)?;
+        let state = self.ctx.create_state()?;
+
+        Ok(Chunks::new(state, source, chunking, decoding))
+    }
+
+    /// Starts a live transcription session, for audio that is fed in as it gets captured.
+    ///
+    /// Word timestamps aren't supported, so `options.word_timestamps` is ignored. Fails with
+    /// [`Error::InvalidOptions`] if `live` can't be used, like a window shorter than what it keeps.
+    pub fn listen(&self, options: &TranscribeOptions, live: LiveOptions) -> Result<Live> {
+        live.validate()?;
+
+        let options = TranscribeOptions {
+            word_timestamps: false,
+            ..options.clone()
+        };
+        let decoding = Decoding::new(&self.ctx, self.lang, &options)?;
+        let state = self.ctx.create_state()?;
+
+        Ok(Live::new(state, live, decoding))
+    }
+}
+
+/// Reads the segments produced by the last inference run on `state`, along with their tokens.
+/// With `dtw`, tokens are timed by the DTW alignment instead of whisper's token timestamps, each
+/// lasting until the next one starts.
+pub(crate) fn segments(
+    state: &WhisperState,
+    token_timestamps: bool,
+    dtw: bool,
+) -> Result<Vec<Utternace>> {
+    let num_segments = state.full_n_segments()?;
+
+    (0..num_segments)
+        .map(|s| {
+            let num_tokens = state.full_n_tokens(s)?;
+            let start = state.full_get_segment_t0(s)?;
+            let stop = state.full_get_segment_t1(s)?;
+
+            let data = (0..num_tokens)
+                .map(|t| state.full_get_token_data(s, t))
+                .collect::<Result<Vec<_>, _>>()?;
+            // DTW only gives the time each token starts at, or -1 when it couldn't align it
+            let aligned = |t: usize| {
+                data.get(t)
+                    .map_or(stop, |data| data.t_dtw)
+                    .clamp(start, stop)
+            };
+
+            let tokens = data
+                .iter()
+                .enumerate()
+                .map(|(t, data)| {
+                    let (t0, t1) = if dtw && data.t_dtw >= 0 {
+                        (aligned(t), aligned(t + 1).max(aligned(t)))
+                    } else {
+                        (data.t0, data.t1)
+                    };
+
+                    // tokens can end halfway through a multi-byte character
+                    let bytes =
+                        state.full_get_token_bytes(s, c_int::try_from(t).unwrap_or_default())?;
+
+                    Ok(Token {
+                        id: data.id,
+                        p: data.p,
+                        plog: data.plog,
+                        text: String::from_utf8_lossy(&bytes).into_owned(),
+                        start: token_timestamps.then_some(t0),
+                        stop: token_timestamps.then_some(t1),
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(Utternace {
+                tokens,
+                start,
+                stop,
+                speaker: None,
+                confidence: None,
+                utterance: None,
+                text: state.full_get_segment_text(s)?,
+            })
+        })
+        .collect()
+}
+
+/// The raw text of every token produced by the last inference run on `state`, by segment. Unlike
+/// their `text`, these can be put back together into the characters they split.
+fn segment_token_bytes(state: &WhisperState) -> Result<Vec<Vec<Vec<u8>>>> {
+    (0..state.full_n_segments()?)
+        .map(|s| {
+            (0..state.full_n_tokens(s)?)
+                .map(|t| Ok(state.full_get_token_bytes(s, t)?))
+                .collect()
+        })
+        .collect()
+}
+
+/// The alignment heads whisper.cpp knows for a model size, which DTW needs. Distilled models
+/// have fewer decoder layers, so the standard heads don't apply to them, and it's unclear which
+/// weights `large` refers to.
+const fn alignment_heads(size: Size) -> Option<DtwModelPreset> {
+    Some(match size {
+        Size::TinyEnglish => DtwModelPreset::TinyEn,
+        Size::Tiny => DtwModelPreset::Tiny,
+        Size::BaseEnglish => DtwModelPreset::BaseEn,
+        Size::Base => DtwModelPreset::Base,
+        Size::SmallEnglish => DtwModelPreset::SmallEn,
+        Size::Small => DtwModelPreset::Small,
+        Size::MediumEnglish => DtwModelPreset::MediumEn,
+        Size::Medium => DtwModelPreset::Medium,
+        Size::LargeV1 => DtwModelPreset::LargeV1,
+        Size::LargeV2 => DtwModelPreset::LargeV2,
+        Size::LargeV3 => DtwModelPreset::LargeV3,
+        Size::LargeV3Turbo => DtwModelPreset::LargeV3Turbo,
+        Size::Large | Size::DistilMediumEnglish | Size::DistilLargeV2 | Size::DistilLargeV3 => {
+            return None
+        }
+    })
+}
+
+/// Shortest audio whisper will run the encoder on, a bit over a second.
+pub(crate) const MIN_INFERENCE_SAMPLES: usize = 17_600;
+
+/// How much of the audio whisper listens to when detecting the language.
+const DETECTION_WINDOW: Duration = Duration::from_secs(30);
+
+struct Detection {
+    result: c_int,
+    probabilities: Vec<f32>,
+}
+
+/// Runs language detection on the mel spectrogram whisper just computed, storing the results in
+/// `user_data` (a [`Detection`]), and stops the inference run before anything gets decoded.
+unsafe extern "C" fn detect_language(
+    ctx: *mut whisper_context,
+    state: *mut whisper_state,
+    user_data: *mut c_void,
+) -> bool {
+    let detection = &mut *user_data.cast::<Detection>();
+    let threads = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(4);
+
+    detection.result = whisper_rs_sys::whisper_lang_auto_detect_with_state(
+        ctx,
+        state,
+        0,
+        c_int::try_from(threads).unwrap_or(1),
+        detection.probabilities.as_mut_ptr(),
+    );
+
+    false
+}
+
+/// Everything needed to build the [`FullParams`] for an inference run, which borrow from it.
+pub(crate) struct Decoding {
+    lang: Option<Language>,
+    options: TranscribeOptions,
+    prompt: Vec<c_int>,
+    suppressed: Vec<c_int>,
+}
+
+impl Decoding {
+    pub fn new(
+        ctx: &WhisperContext,
+        lang: Option<Language>,
+        options: &TranscribeOptions,
+    ) -> Result<Self> {
+        // a token is never shorter than a byte
+        let prompt = match &options.initial_prompt {
+            Some(prompt) => ctx.tokenize(prompt, prompt.len())?,
+            None => Vec::new(),
+        };
+
+        let suppressed = options
+            .suppress_tokens
+            .iter()
+            .filter(|&&token| token != -1)
+            .map(|&token| {
+                if (0..ctx.n_vocab()).contains(&token) {
+                    Ok(token)
+                } else {
+                    Err(Error::InvalidToken(token))
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            lang,
+            prompt,
+            suppressed,
+            options: options.clone(),
+        })
+    }
+
+    pub fn params(&self) -> FullParams<'_, '_> {
+        let options = &self.options;
+        let mut params = FullParams::new(match options.beam_size {
+            Some(beam_size) => SamplingStrategy::BeamSearch {
+                beam_size: c_int::try_from(beam_size).unwrap_or(c_int::MAX),
+                patience: options.patience.unwrap_or(-1.0),
+            },
+            None => SamplingStrategy::Greedy {
+                best_of: c_int::try_from(options.best_of).unwrap_or(c_int::MAX),
+            },
+        });
+
+        params.set_translate(options.translate);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        params.set_token_timestamps(options.word_timestamps);
+        params.set_language(self.lang.map(Into::into));
+
+        params.set_temperature(options.temperature);
+        params.set_temperature_inc(options.temperature_increment);
+        params.set_entropy_thold(options.entropy_threshold);
+        params.set_logprob_thold(options.logprob_threshold);
+        params.set_no_speech_thold(options.no_speech_threshold);
+
+        if !self.prompt.is_empty() {
+            params.set_tokens(&self.prompt);
+        }
+
+        if let Some(length) = options.max_segment_length {
+            // splitting segments relies on token timestamps
+            params.set_token_timestamps(true);
+            params.set_split_on_word(true);
+            params.set_max_len(c_int::try_from(length).unwrap_or(c_int::MAX));
+        }
+
+        params.set_suppress_nst(options.suppress_tokens.contains(&-1));
+        if !self.suppressed.is_empty() {
+            // SAFETY: the callback only reads the token list, which outlives the params since
+            // they borrow from `self`.
+            unsafe {
+                params.set_filter_logits_callback(Some(suppress_tokens));
+                params.set_filter_logits_callback_user_data(
+                    std::ptr::addr_of!(self.suppressed)
+                        .cast_mut()
+                        .cast::<c_void>(),
+                );
+            }
+        }
+
+        params
+    }
+}
+
+/// Keeps whisper from generating the tokens in `user_data`, a `Vec<c_int>` of valid token ids.
+unsafe extern "C" fn suppress_tokens(
+    _ctx: *mut whisper_context,
+    _state: *mut whisper_state,
+    _tokens: *const whisper_token_data,
+    _n_tokens: c_int,
+    logits: *mut f32,
+    user_data: *mut c_void,
+) {
+    let tokens = &*user_data.cast::<Vec<c_int>>();
+
+    for &token in tokens {
+        #[allow(clippy::cast_sign_loss)]
+        logits.add(token as usize).write(f32::NEG_INFINITY);
+    }
 }
@@ -0,0 +1,315 @@
+use crate::{
+    transcript::{Token, Utternace},
+    Language,
+};
+
+/// Punctuation that belongs to the word after it, rather than the one before it.
+const OPENING_PUNCTUATION: &[char] = &['"', '\'', '“', '‘', '«', '¿', '¡', '(', '[', '{'];
+
+/// Merges the tokens of each utterance into whole words, with punctuation attached to the word
+/// it belongs to.
+///
+/// `token_bytes` holds the raw text of every token, by segment. Tokens can end halfway through a
+/// multi-byte character, which leaves replacement characters in their `text`, so the words are
+/// put together from these instead when available.
+pub(crate) fn group_words(
+    utterances: &[Utternace],
+    token_bytes: &[Vec<Vec<u8>>],
+    lang: Option<Language>,
+) -> Vec<Utternace> {
+    let unspaced = is_unspaced(lang);
+
+    let mut words = Vec::new();
+    for (segment, utterance) in utterances.iter().enumerate() {
+        // words never span utterances
+        let mut word: Option<Word> = None;
+
+        for (i, token) in utterance.tokens.iter().enumerate() {
+            let bytes = token_bytes
+                .get(segment)
+                .and_then(|tokens| tokens.get(i))
+                .map_or(token.text.as_bytes(), Vec::as_slice);
+            if bytes.is_empty() || bytes.starts_with(b"[_") {
+                continue;
+            }
+
+            let piece = Piece::new(bytes);
+            let starts_word = match &word {
+                None => true,
+                Some(_) if piece.closing => false,
+                Some(current) if current.is_opening() => false,
+                Some(current) => piece.spaced || (unspaced && current.is_complete()),
+            };
+
+            if starts_word {
+                words.extend(word.take().map(|word| word.finish(segment)));
+            }
+
+            word.get_or_insert_with(Word::default)
+                .push(token, bytes, &piece);
+        }
+
+        words.extend(word.map(|word| word.finish(segment)));
+    }
+
+    words
+}
+
+/// Whether `lang` is written without spaces between words, so that every character counts as a
+/// word.
+pub(crate) const fn is_unspaced(lang: Option<Language>) -> bool {
+    matches!(
+        lang,
+        Some(
+            Language::Chinese
+                | Language::Japanese
+                | Language::Thai
+                | Language::Lao
+                | Language::Myanmar
+        )
+    )
+}
+
+/// What a token's text says about where it goes.
+struct Piece {
+    /// Starts with a space, so it's the start of a new word.
+    spaced: bool,
+    /// Punctuation that belongs to the word before it, like a comma.
+    closing: bool,
+    /// Punctuation that belongs to the word after it (when it starts with a space), like an
+    /// opening quote.
+    opening: bool,
+    /// Nothing but whitespace.
+    blank: bool,
+}
+
+impl Piece {
+    fn new(bytes: &[u8]) -> Self {
+        let spaced = bytes.first() == Some(&b' ');
+        let text = std::str::from_utf8(bytes).ok().map(str::trim);
+        let blank = text.is_some_and(str::is_empty);
+        let text = text.unwrap_or_default();
+        let punctuation = !text.is_empty() && text.chars().all(is_punctuation);
+        let opening = punctuation && text.chars().all(|c| OPENING_PUNCTUATION.contains(&c));
+
+        Self {
+            blank,
+            spaced,
+            opening,
+            closing: punctuation && !opening,
+        }
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(
+            c,
+            '…' | '“'
+                | '”'
+                | '‘'
+                | '’'
+                | '«'
+                | '»'
+                | '¿'
+                | '¡'
+                | '。'
+                | '，'
+                | '、'
+                | '！'
+                | '？'
+        )
+}
+
+#[derive(Default)]
+struct Word {
+    bytes: Vec<u8>,
+    tokens: Vec<Token>,
+    /// Whether each token is punctuation or whitespace, which don't count towards the word's
+    /// timing.
+    untimed: Vec<bool>,
+}
+
+impl Word {
+    fn push(&mut self, token: &Token, bytes: &[u8], piece: &Piece) {
+        // the space before a word isn't part of it, and neither is the one before punctuation
+        let bytes = if self.bytes.is_empty() || piece.closing {
+            bytes.strip_prefix(b" ").unwrap_or(bytes)
+        } else {
+            bytes
+        };
+
+        self.bytes.extend_from_slice(bytes);
+        self.tokens.push(token.clone());
+        self.untimed
+            .push(piece.opening || piece.closing || piece.blank);
+    }
+
+    /// Whether the word is only opening punctuation so far, waiting for the word it belongs to.
+    fn is_opening(&self) -> bool {
+        std::str::from_utf8(&self.bytes).is_ok_and(|text| {
+            text.chars()
+                .all(|c| c.is_whitespace() || OPENING_PUNCTUATION.contains(&c))
+        })
+    }
+
+    /// Whether the word doesn't end halfway through a character.
+    fn is_complete(&self) -> bool {
+        std::str::from_utf8(&self.bytes).is_ok()
+    }
+
+    /// The finished word, spoken in the `utterance`th utterance.
+    fn finish(self, utterance: usize) -> Utternace {
+        let timed = self
+            .tokens
+            .iter()
+            .zip(&self.untimed)
+            .filter(|(_, untimed)| !**untimed)
+            .map(|(token, _)| token)
+            .collect::<Vec<_>>();
+        let timed = if timed.is_empty() {
+            self.tokens.iter().collect()
+        } else {
+            timed
+        };
+
+        #[allow(clippy::cast_precision_loss)]
+        let confidence =
+            self.tokens.iter().map(|token| token.p).sum::<f32>() / self.tokens.len() as f32;
+
+        Utternace {
+            text: String::from_utf8_lossy(&self.bytes).into_owned(),
+            start: timed
+                .first()
+                .and_then(|token| token.start)
+                .unwrap_or_default(),
+            stop: timed
+                .last()
+                .and_then(|token| token.stop)
+                .unwrap_or_default(),
+            confidence: Some(confidence),
+            tokens: self.tokens,
+            speaker: None,
+            utterance: Some(utterance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(text: &str, start: i64, stop: i64) -> Token {
+        Token {
+            id: 0,
+            text: text.to_string(),
+            p: 1.0,
+            plog: 0.0,
+            start: Some(start),
+            stop: Some(stop),
+        }
+    }
+
+    fn utterance(tokens: Vec<Token>) -> Utternace {
+        Utternace {
+            start: 0,
+            stop: 0,
+            text: String::new(),
+            tokens,
+            speaker: None,
+            confidence: None,
+            utterance: None,
+        }
+    }
+
+    fn words(words: &[Utternace]) -> Vec<(&str, i64, i64)> {
+        words
+            .iter()
+            .map(|word| (word.text.as_str(), word.start, word.stop))
+            .collect()
+    }
+
+    #[test]
+    fn starts_words_at_spaces() {
+        let utterances = [
+            utterance(vec![
+                token("[_BEG_]", 0, 0),
+                token(" Hello", 0, 10),
+                token(" wor", 10, 20),
+                token("ld", 20, 30),
+            ]),
+            utterance(vec![token(" Bye", 40, 50)]),
+        ];
+        let grouped = group_words(&utterances, &[], None);
+
+        assert_eq!(
+            words(&grouped),
+            [("Hello", 0, 10), ("world", 10, 30), ("Bye", 40, 50)]
+        );
+        let utterances = grouped
+            .iter()
+            .map(|word| word.utterance)
+            .collect::<Vec<_>>();
+        assert_eq!(utterances, [Some(0), Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn attaches_punctuation_to_its_word_without_timing_it() {
+        let utterances = [utterance(vec![
+            token(" Hello", 0, 10),
+            token(",", 10, 11),
+            token(" \"", 11, 12),
+            token("world", 12, 20),
+            token("!\"", 20, 25),
+        ])];
+
+        assert_eq!(
+            words(&group_words(&utterances, &[], None)),
+            [("Hello,", 0, 10), ("\"world!\"", 12, 20)]
+        );
+    }
+
+    #[test]
+    fn joins_characters_split_between_tokens() {
+        let utterances = [utterance(vec![
+            token(" caf", 0, 10),
+            token("\u{fffd}", 10, 15),
+            token("\u{fffd}", 15, 20),
+            token(" au", 20, 25),
+            token(" lait", 25, 30),
+        ])];
+        let bytes = [vec![
+            b" caf".to_vec(),
+            vec![0xc3],
+            vec![0xa9],
+            b" au".to_vec(),
+            b" lait".to_vec(),
+        ]];
+
+        assert_eq!(
+            words(&group_words(&utterances, &bytes, None)),
+            [("café", 0, 20), ("au", 20, 25), ("lait", 25, 30)]
+        );
+    }
+
+    #[test]
+    fn splits_unspaced_languages_between_tokens() {
+        let utterances = [utterance(vec![
+            token("こん", 0, 10),
+            token("\u{fffd}", 10, 15),
+            token("\u{fffd}", 15, 20),
+            token("。", 20, 21),
+        ])];
+        let bytes = [vec![
+            "こん".as_bytes().to_vec(),
+            vec![0xe3, 0x81],
+            vec![0xaf],
+            "。".as_bytes().to_vec(),
+        ]];
+
+        assert_eq!(
+            words(&group_words(&utterances, &bytes, Some(Language::Japanese))),
+            [("こん", 0, 10), ("は。", 10, 20)]
+        );
+    }
+}